@@ -0,0 +1,186 @@
+// A fixed-size set of small integers, stored as packed bits.
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    // An empty set that can hold 0..len
+    pub fn new(len: usize) -> BitSet {
+        BitSet { words: vec![0; len.div_ceil(64)], len }
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    pub fn contains(&self, n: usize) -> bool {
+        self.words[n / 64] & (1 << (n % 64)) != 0
+    }
+
+    // Returns true if n was not already in the set.
+    pub fn insert(&mut self, n: usize) -> bool {
+        let was_set = self.contains(n);
+        self.words[n / 64] |= 1 << (n % 64);
+        !was_set
+    }
+
+    pub fn remove(&mut self, n: usize) {
+        self.words[n / 64] &= !(1 << (n % 64));
+    }
+
+    pub fn set(&mut self, n: usize, value: bool) {
+        if value {
+            self.insert(n);
+        }
+        else {
+            self.remove(n);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.words.iter_mut().for_each(|w| *w = 0);
+    }
+
+    pub fn union_with(&mut self, other: &BitSet) {
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            *w |= o;
+        }
+    }
+
+    pub fn intersect_with(&mut self, other: &BitSet) {
+        for (w, o) in self.words.iter_mut().zip(&other.words) {
+            *w &= o;
+        }
+    }
+
+    // Adds every member of other moved up by 'by' (or down, if negative).
+    // Members moved outside 0..len are dropped.
+    pub fn union_shifted(&mut self, other: &BitSet, by: isize) {
+        assert_eq!(self.len, other.len);
+
+        let n = self.words.len();
+        let word_shift = by.unsigned_abs() / 64;
+        let bit_shift = (by.unsigned_abs() % 64) as u32;
+
+        for i in 0..n {
+            let word = if by >= 0 {
+                if i < word_shift {
+                    continue;
+                }
+                let low = if bit_shift > 0 && i > word_shift { other.words[i - word_shift - 1] >> (64 - bit_shift) } else { 0 };
+                (other.words[i - word_shift] << bit_shift) | low
+            }
+            else {
+                if i + word_shift >= n {
+                    break;
+                }
+                let high = if bit_shift > 0 && i + word_shift + 1 < n { other.words[i + word_shift + 1] << (64 - bit_shift) } else { 0 };
+                (other.words[i + word_shift] >> bit_shift) | high
+            };
+            self.words[i] |= word;
+        }
+
+        // Drop anything shifted past the end.
+        if !self.len.is_multiple_of(64) {
+            self.words[n - 1] &= (1 << (self.len % 64)) - 1;
+        }
+    }
+
+    // Number of members
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    // Members in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(index, word)| {
+            let mut bits = *word;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    None
+                }
+                else {
+                    let bit = bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+                    Some(index * 64 + bit)
+                }
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BitSet;
+
+    #[test]
+    fn test_insert_remove() {
+        let mut set = BitSet::new(130);
+        assert!(set.is_empty());
+
+        assert!(set.insert(0));
+        assert!(set.insert(64));
+        assert!(set.insert(129));
+        assert!(!set.insert(64));
+        assert_eq!(set.count(), 3);
+        assert!(set.contains(129));
+        assert!(!set.contains(128));
+
+        set.remove(64);
+        set.set(5, true);
+        assert_eq!(set.iter().collect::<Vec<usize>>(), vec![0, 5, 129]);
+
+        set.clear();
+        assert!(set.is_empty());
+        assert_eq!(set.len(), 130);
+    }
+
+    #[test]
+    fn test_union_intersect() {
+        let mut a = BitSet::new(100);
+        let mut b = BitSet::new(100);
+        a.insert(1);
+        a.insert(70);
+        b.insert(70);
+        b.insert(99);
+
+        let mut union = a.clone();
+        union.union_with(&b);
+        assert_eq!(union.iter().collect::<Vec<usize>>(), vec![1, 70, 99]);
+
+        a.intersect_with(&b);
+        assert_eq!(a.iter().collect::<Vec<usize>>(), vec![70]);
+    }
+
+    #[test]
+    fn test_union_shifted() {
+        let mut a = BitSet::new(130);
+        for n in [0, 5, 63, 64, 100, 129] {
+            a.insert(n);
+        }
+
+        for by in [-130, -65, -64, -63, -1, 0, 1, 30, 64, 66, 200] {
+            let mut shifted = BitSet::new(130);
+            shifted.insert(7);
+            shifted.union_shifted(&a, by);
+
+            let mut expected: Vec<usize> = a.iter()
+                .map(|n| n as isize + by)
+                .filter(|n| (0..130).contains(n))
+                .map(|n| n as usize)
+                .chain([7])
+                .collect();
+            expected.sort();
+            expected.dedup();
+            assert_eq!(shifted.iter().collect::<Vec<usize>>(), expected, "by {by}");
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::{collections::HashMap, hash::Hash};
+
+// Cycle detection for deterministic simulations.
+//
+// A simulation starts in some state and repeatedly applies a step function.
+// Since the step is deterministic, once a state repeats the simulation loops
+// forever.  The states before the loop are the prefix, and the loop length
+// is the period:
+//
+//   state[n] == state[n + period]  for all n >= prefix
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Cycle {
+    pub prefix: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    // The earliest step with the same state as `step`.
+    pub fn reduce(&self, step: usize) -> usize {
+        if step < self.prefix {
+            step
+        }
+        else {
+            self.prefix + (step - self.prefix) % self.period
+        }
+    }
+
+    // Runs the step function from start to reach the state at `step`.
+    pub fn state_at<S, F>(&self, start: &S, mut step_fn: F, step: usize) -> S
+    where
+        S: Clone,
+        F: FnMut(&S) -> S,
+    {
+        let mut state = start.clone();
+        for _ in 0..self.reduce(step) {
+            state = step_fn(&state);
+        }
+
+        state
+    }
+}
+
+// A cycle along with every state visited before the first repeat.
+// states[n] is the state after n steps, for n < prefix + period.
+pub struct CycleHistory<S> {
+    pub cycle: Cycle,
+    pub states: Vec<S>,
+}
+
+impl<S> CycleHistory<S> {
+    pub fn state_at(&self, step: usize) -> &S {
+        &self.states[self.cycle.reduce(step)]
+    }
+}
+
+// Incremental history-map detector, for simulations that can't be driven
+// by a step function.  Feed it one state per step.
+pub struct Detector<S> {
+    seen: HashMap<S, usize>,
+    steps: usize,
+    cycle: Option<Cycle>,
+}
+
+impl<S: Eq + Hash> Detector<S> {
+    pub fn new() -> Detector<S> {
+        Detector { seen: HashMap::new(), steps: 0, cycle: None }
+    }
+
+    // Records the state at the next step.  Returns the cycle once a state repeats.
+    pub fn observe(&mut self, state: S) -> Option<Cycle> {
+        if self.cycle.is_none() {
+            match self.seen.get(&state) {
+                Some(first) => {
+                    self.cycle = Some(Cycle { prefix: *first, period: self.steps - first });
+                }
+                None => {
+                    self.seen.insert(state, self.steps);
+                }
+            }
+        }
+        self.steps += 1;
+
+        self.cycle
+    }
+
+    pub fn cycle(&self) -> Option<Cycle> {
+        self.cycle
+    }
+}
+
+impl<S: Eq + Hash> Default for Detector<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// History-map strategy.  Keeps every state, which costs memory but finds the
+// cycle in prefix + period steps and allows direct lookup of any state.
+// States are compared in full, not by hash value.
+pub fn with_history<S, F>(start: S, mut step_fn: F) -> CycleHistory<S>
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    let mut seen: HashMap<S, usize> = HashMap::new();
+    let mut states: Vec<S> = Vec::new();
+    let mut state = start;
+
+    loop {
+        if let Some(first) = seen.get(&state) {
+            let cycle = Cycle { prefix: *first, period: states.len() - first };
+            return CycleHistory { cycle, states };
+        }
+
+        let next = step_fn(&state);
+        seen.insert(state.clone(), states.len());
+        states.push(state);
+        state = next;
+    }
+}
+
+// Brent's algorithm.  Uses constant memory, at the cost of rerunning steps:
+// the loop is found by comparing against saved states at powers of two.
+pub fn brent<S, F>(start: &S, mut step_fn: F) -> Cycle
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> S,
+{
+    // Find the period: search powers of two for a window containing the loop.
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = start.clone();
+    let mut hare = step_fn(start);
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step_fn(&hare);
+        period += 1;
+    }
+
+    // Find the prefix: start the hare one period ahead and move both together.
+    tortoise = start.clone();
+    hare = start.clone();
+    for _ in 0..period {
+        hare = step_fn(&hare);
+    }
+    let mut prefix = 0;
+    while tortoise != hare {
+        tortoise = step_fn(&tortoise);
+        hare = step_fn(&hare);
+        prefix += 1;
+    }
+
+    Cycle { prefix, period }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{brent, with_history, Cycle, Detector};
+
+    // 0, 1, 2, ... 6, then loops back to 3.
+    fn rho(n: &usize) -> usize {
+        if *n == 6 { 3 } else { n + 1 }
+    }
+
+    #[test]
+    fn test_strategies_agree() {
+        let expected = Cycle { prefix: 3, period: 4 };
+
+        assert_eq!(with_history(0, rho).cycle, expected);
+        assert_eq!(brent(&0, rho), expected);
+    }
+
+    #[test]
+    fn test_pure_loop() {
+        let step = |n: &usize| (n + 1) % 5;
+        let expected = Cycle { prefix: 0, period: 5 };
+
+        assert_eq!(with_history(0, step).cycle, expected);
+        assert_eq!(brent(&0, step), expected);
+
+        // A fixed point is a loop of length one.
+        assert_eq!(brent(&7, |n: &usize| *n), Cycle { prefix: 0, period: 1 });
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        let history = with_history(0, rho);
+        assert_eq!(history.states, vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(*history.state_at(2), 2);
+        assert_eq!(*history.state_at(7), 3);
+        assert_eq!(*history.state_at(1_000_000_000), 3 + (1_000_000_000 - 3) % 4);
+
+        let cycle = brent(&0, rho);
+        for n in 0..50 {
+            assert_eq!(cycle.state_at(&0, rho, n), *history.state_at(n));
+        }
+    }
+
+    #[test]
+    fn test_detector() {
+        let mut detector: Detector<usize> = Detector::new();
+        let mut state = 0;
+        let mut found = None;
+        while found.is_none() {
+            found = detector.observe(state);
+            state = rho(&state);
+        }
+
+        assert_eq!(found, Some(Cycle { prefix: 3, period: 4 }));
+        assert_eq!(detector.cycle(), found);
+    }
+}
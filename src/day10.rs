@@ -1,6 +1,6 @@
 use std::{fs::File, io::{BufReader, BufRead}};
 
-use crate::day::{Day, Answer};
+use crate::{day::{Day, Answer}, polygon::Polygon};
 
 #[derive(Debug)]
 enum Direction {
@@ -18,66 +18,6 @@ enum Pipe {
     EW,
 }
 
-#[derive(Debug)]
-enum InOutState {
-    Outside,
-    NorthBorder,
-    SouthBorder,
-    Inside
-}
-
-struct InOutFsm {
-    state: InOutState,
-    inside: usize,
-}
-
-impl InOutFsm {
-    fn new() -> InOutFsm {
-        InOutFsm { state: InOutState::Outside, inside: 0}
-    }
-
-    fn process(&mut self, pipes: &Vec<Pipe>) {
-        self.state = InOutState::Outside;
-        let mut index = 0;
-
-        for pipe in pipes {
-            self.state = match (&self.state, pipe) {
-                (InOutState::Outside, Pipe::Ground) => {
-                    // We found an outside bit of ground
-                    InOutState::Outside    // Stay in outside state
-                }
-                (InOutState::Inside, Pipe::Ground) => {
-                    self.inside += 1;         // We found an inside bit of ground
-                    InOutState::Inside     // Stay in inside state
-                }
-                (InOutState::Outside, Pipe::SE) => { InOutState::NorthBorder }
-                (InOutState::Outside, Pipe::NE) => { InOutState::SouthBorder }
-                (InOutState::Outside, Pipe::NS) => { InOutState::Inside }
-                (InOutState::NorthBorder, Pipe::SW) => { InOutState::Outside }
-                (InOutState::NorthBorder, Pipe::EW) => { InOutState::NorthBorder }
-                (InOutState::NorthBorder, Pipe::NW) => { InOutState::Inside }
-                (InOutState::SouthBorder, Pipe::NW) => { InOutState::Outside }
-                (InOutState::SouthBorder, Pipe::EW) => { InOutState::SouthBorder }
-                (InOutState::SouthBorder, Pipe::SW) => { InOutState::Inside }
-                (InOutState::Inside, Pipe::NE) => { InOutState::NorthBorder }
-                (InOutState::Inside, Pipe::SE) => { InOutState::SouthBorder }
-                (InOutState::Inside, Pipe::NS) => { InOutState::Outside }
-                _ => { 
-                    println!("Processing {:?}", pipes);
-                    println!("In state {:?} at index {}", &self.state, index);
-                    panic!("Invalid pipe found.") 
-                }
-            };
-
-            index += 1;
-        }
-    }
-
-    fn get_inside(&self) -> usize {
-        self.inside
-    }
-}
-
 struct Input {
     pipes: Vec<Vec<Pipe>>,
     start: (usize, usize),
@@ -85,83 +25,12 @@ struct Input {
 
 impl Input {
     fn loop_length(&self) -> usize {
-        let mut position = self.start;
-        let mut pipe = &self.pipes[position.0][position.1];
-
-        // choose an initial direction of travel
-        let mut direction = match pipe {
-            Pipe::Ground => { panic!("We shouldn't be here.") }
-            Pipe::NE => { Direction::N }
-            Pipe::SE => { Direction::E }
-            Pipe::NS => { Direction::N }
-            Pipe::NW => { Direction::N }
-            Pipe::SW => { Direction::S }
-            Pipe::EW => { Direction::E }
-        };
-
-        // println!("Starting at ({}, {}), moving {:?}", position.0, position.1, direction);
-
-        let mut steps = 0;
-        loop {
-            // move in the chosen direction
-            position = match direction {
-                Direction::N => { (position.0-1, position.1) }
-                Direction::E => { (position.0,   position.1+1) }
-                Direction::S => { (position.0+1, position.1) }
-                Direction::W => { (position.0,   position.1-1) }
-            };
-            
-            // update the pipe we are at
-            pipe = &self.pipes[position.0][position.1];
-
-            // update the direction we are moving
-            direction = match (&pipe, &direction) {
-                (Pipe::NE, Direction::S) => {Direction::E}
-                (Pipe::NE, Direction::W) => {Direction::N}
-                (Pipe::SE, Direction::N) => {Direction::E}
-                (Pipe::SE, Direction::W) => {Direction::S}
-                (Pipe::NS, Direction::S) => {Direction::S}
-                (Pipe::NS, Direction::N) => {Direction::N}
-                (Pipe::NW, Direction::S) => {Direction::W}
-                (Pipe::NW, Direction::E) => {Direction::N}
-                (Pipe::SW, Direction::N) => {Direction::W}
-                (Pipe::SW, Direction::E) => {Direction::S}
-                (Pipe::EW, Direction::W) => {Direction::W}
-                (Pipe::EW, Direction::E) => {Direction::E}
-                _ => {
-                    println!("At ({}, {}), Pipe {:?}, Direction {:?}",
-                        position.0, position.1, &pipe, &direction);
-
-                    panic!();
-                }
-            };
-            // println!("    at ({}, {}), moving {:?}", position.0, position.1, direction);
-
-            // count that step
-            steps += 1;
-
-            // break out if we are back at the start
-            if position == self.start {
-                break;
-            }
-        }
-
-        steps
+        self.loop_path().len()
     }
 
-    // Produce a cleaned up version of the input with the single loop isolated.
-    fn isolated(&self) -> Input {
-        // Create a blank map, with ground everywhere.
-        let mut cleaned: Vec<Vec<Pipe>> = Vec::new();
-        for row in &self.pipes {
-            let mut clean_row = Vec::new();
-            for _pipe in row {
-                clean_row.push(Pipe::Ground);
-            }
-            cleaned.push(clean_row);
-        }
-
-        // Copy the path elements into the blank map
+    // Positions along the loop through the start, ending back at the start.
+    fn loop_path(&self) -> Vec<(usize, usize)> {
+        let mut path: Vec<(usize, usize)> = Vec::new();
         let mut position = self.start;
         let mut pipe = &self.pipes[position.0][position.1];
 
@@ -213,8 +82,7 @@ impl Input {
             };
             // println!("    at ({}, {}), moving {:?}", position.0, position.1, direction);
 
-            // copy this pipe
-            cleaned[position.0][position.1] = self.pipes[position.0][position.1];
+            path.push(position);
 
             // break out if we are back at the start
             if position == self.start {
@@ -222,21 +90,18 @@ impl Input {
             }
         }
 
-
-        Input { pipes: cleaned, start: self.start }
+        path
     }
 
+    // Ground (or pipe not in the loop) enclosed by the loop.  The centres of
+    // the loop's tiles are the corners of a polygon, with nothing but the
+    // loop itself on its edges, so this is the polygon's interior.
     fn enclosed(&self) -> usize {
-        // Identify the loop.
-        // Make a clean copy with only the loop and open ground
-        let clean_input = self.isolated();
-
-        let mut fsm = InOutFsm::new();
-        for row in &clean_input.pipes {
-            fsm.process(row);
-        }
+        let vertices = self.loop_path().iter()
+            .map(|(row, col)| (*row as isize, *col as isize))
+            .collect();
 
-        fsm.get_inside()
+        Polygon::new(vertices).interior()
     }
 }
 
@@ -370,6 +235,17 @@ mod test {
         assert_eq!(input2.loop_length(), 16);
     }
 
+    #[test]
+    fn test_loop_path() {
+        let input = Day10::read_input("examples/day10_example1.txt");
+        let path = input.loop_path();
+
+        assert_eq!(path.len(), 8);
+        assert_eq!(*path.last().unwrap(), input.start);
+        assert!(path.contains(&(3, 3)));
+        assert!(!path.contains(&(2, 2)));
+    }
+
     #[test]
     fn test_part1_ex1() {
         let d = Day10::new("examples/day10_example1.txt");
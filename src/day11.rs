@@ -8,6 +8,145 @@ struct Input {
     galaxy_positions: Vec<(usize, usize)>,
 }
 
+// Distance in the expanded universe as a function of the expansion factor:
+//   distance(factor) = base + (factor-1) * empty
+// where base is the unexpanded distance and empty is the number of empty rows
+// and columns crossed.  Each empty row or column grows to `factor` wide.
+// Empty lines can't shrink, so a factor of 0 counts as 1.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct DistanceFormula {
+    base: usize,
+    empty: usize,
+}
+
+impl DistanceFormula {
+    fn at(&self, factor: usize) -> u128 {
+        self.base as u128 + factor.saturating_sub(1) as u128 * self.empty as u128
+    }
+}
+
+impl Input {
+    // empty_before[i] is the number of empty lines (rows or cols) before line i.
+    fn empty_before(&self, len: usize, coord: fn(&(usize, usize)) -> usize) -> Vec<usize> {
+        let mut occupied = vec![false; len];
+        for pos in &self.galaxy_positions {
+            occupied[coord(pos)] = true;
+        }
+
+        let mut empty_before = Vec::with_capacity(len);
+        let mut count = 0;
+        for occ in occupied {
+            empty_before.push(count);
+            if !occ {
+                count += 1;
+            }
+        }
+
+        empty_before
+    }
+
+    fn empty_rows_before(&self) -> Vec<usize> {
+        self.empty_before(self.rows, |(row, _col)| *row)
+    }
+
+    fn empty_cols_before(&self) -> Vec<usize> {
+        self.empty_before(self.cols, |(_row, col)| *col)
+    }
+
+    // Closed form for the sum of distances over all pairs of galaxies.
+    // Each axis is handled separately.  Sorting the coordinates also sorts the
+    // empty-line counts, so the sum of pairwise differences is
+    //   sum over k of x[k] * (2k - n + 1)
+    fn sum_formula(&self) -> DistanceFormula {
+        let empty_rows = self.empty_rows_before();
+        let empty_cols = self.empty_cols_before();
+
+        let mut rows: Vec<usize> = self.galaxy_positions.iter().map(|(row, _)| *row).collect();
+        let mut cols: Vec<usize> = self.galaxy_positions.iter().map(|(_, col)| *col).collect();
+        rows.sort();
+        cols.sort();
+
+        let pair_sum = |sorted: &[usize], f: &dyn Fn(usize) -> usize| -> usize {
+            let n = sorted.len() as isize;
+            let sum: isize = sorted.iter().enumerate()
+                .map(|(k, x)| f(*x) as isize * (2 * k as isize - n + 1))
+                .sum();
+            sum as usize
+        };
+
+        let base = pair_sum(&rows, &|row| row) + pair_sum(&cols, &|col| col);
+        let empty = pair_sum(&rows, &|row| empty_rows[row]) + pair_sum(&cols, &|col| empty_cols[col]);
+
+        DistanceFormula { base, empty }
+    }
+}
+
+// Distances between particular galaxies.  The answers only need the sum, so
+// these are for checking it.
+#[cfg(test)]
+impl Input {
+    // Distance formula between two galaxies, given by index.
+    fn pair_formula(&self, galaxy1: usize, galaxy2: usize) -> DistanceFormula {
+        let empty_rows = self.empty_rows_before();
+        let empty_cols = self.empty_cols_before();
+
+        Self::pair_formula_with(&empty_rows, &empty_cols,
+            self.galaxy_positions[galaxy1], self.galaxy_positions[galaxy2])
+    }
+
+    fn pair_formula_with(empty_rows: &[usize], empty_cols: &[usize],
+            (row1, col1): (usize, usize), (row2, col2): (usize, usize)) -> DistanceFormula {
+        let base = row1.abs_diff(row2) + col1.abs_diff(col2);
+        let empty = empty_rows[row1].abs_diff(empty_rows[row2]) +
+                    empty_cols[col1].abs_diff(empty_cols[col2]);
+
+        DistanceFormula { base, empty }
+    }
+
+    fn distance(&self, galaxy1: usize, galaxy2: usize, factor: usize) -> u128 {
+        self.pair_formula(galaxy1, galaxy2).at(factor)
+    }
+
+    // distance_matrix[i][j] is the distance between galaxies i and j.
+    fn distance_matrix(&self, factor: usize) -> Vec<Vec<u128>> {
+        let empty_rows = self.empty_rows_before();
+        let empty_cols = self.empty_cols_before();
+
+        self.galaxy_positions.iter()
+            .map(|g1| {
+                self.galaxy_positions.iter()
+                    .map(|g2| Self::pair_formula_with(&empty_rows, &empty_cols, *g1, *g2).at(factor))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Returns (galaxy1, galaxy2, distance) for the pair that is best according to `better`.
+    fn extreme_pair(&self, factor: usize, better: fn(u128, u128) -> bool) -> Option<(usize, usize, u128)> {
+        let matrix = self.distance_matrix(factor);
+        let mut best: Option<(usize, usize, u128)> = None;
+
+        for (galaxy1, row) in matrix.iter().enumerate() {
+            for (galaxy2, &dist) in row.iter().enumerate().skip(galaxy1+1) {
+                match best {
+                    Some((_, _, best_dist)) if !better(dist, best_dist) => {}
+                    _ => best = Some((galaxy1, galaxy2, dist)),
+                }
+            }
+        }
+
+        best
+    }
+
+    fn nearest_pair(&self, factor: usize) -> Option<(usize, usize, u128)> {
+        self.extreme_pair(factor, |a, b| a < b)
+    }
+
+    fn farthest_pair(&self, factor: usize) -> Option<(usize, usize, u128)> {
+        self.extreme_pair(factor, |a, b| a > b)
+    }
+}
+
 pub struct Day11<'a> {
     _input_filename: &'a str,
 }
@@ -44,43 +183,7 @@ impl<'a> Day11<'a> {
     }
 
     fn dist_sums(input: &Input, expansion: isize) -> usize {
-        // create vectors of col_widths and row_widths, initialize all to two.
-        let mut col_width: Vec<isize> = vec![expansion; input.cols];
-        let mut row_width: Vec<isize> = vec![expansion; input.rows];
-        
-        // rows and columns that are non-empty should have width 1
-        for (row, col) in &input.galaxy_positions {
-            col_width[*col] = 1;
-            row_width[*row] = 1;
-        }
-
-        // create vectors of col_pos, row_pos.
-        let mut col_pos: Vec<isize> = vec![0; input.cols];
-        let mut row_pos: Vec<isize> = vec![0; input.rows];
-        let mut pos = 0;
-        for col_no in 0.. input.cols {
-            col_pos[col_no] = pos;
-            pos += col_width[col_no];
-        }
-
-        let mut pos = 0;
-        for row_no in 0.. input.rows {
-            row_pos[row_no] = pos;
-            pos += row_width[row_no];
-        }
-
-        // For all pairs of galaxies...
-        let mut total_dist: isize = 0;
-        for galaxy1 in 0..input.galaxy_positions.len()-1 {
-            for galaxy2 in galaxy1+1 .. input.galaxy_positions.len() {
-                let (row1, col1) = input.galaxy_positions[galaxy1];
-                let (row2, col2) = input.galaxy_positions[galaxy2];
-                total_dist += (row_pos[row1] - row_pos[row2]).abs() + 
-                              (col_pos[col1] - col_pos[col2]).abs();
-            }
-        }
-        
-        total_dist as usize
+        input.sum_formula().at(expansion as usize) as usize
     }
 }
 
@@ -128,6 +231,48 @@ mod test {
         assert_eq!(Day11::dist_sums(&input, 100), 8410);
     }
 
+    #[test]
+    fn test_pair_distances() {
+        let input = Day11::read_input("examples/day11_example1.txt");
+
+        assert_eq!(input.distance(4, 8, 2), 9);
+        assert_eq!(input.distance(0, 6, 2), 15);
+        assert_eq!(input.distance(2, 5, 2), 17);
+        assert_eq!(input.distance(7, 8, 2), 5);
+
+        let matrix = input.distance_matrix(2);
+        assert_eq!(matrix[4][8], 9);
+        assert_eq!(matrix[8][4], 9);
+        assert_eq!(matrix[3][3], 0);
+    }
+
+    #[test]
+    fn test_extreme_pairs() {
+        let input = Day11::read_input("examples/day11_example1.txt");
+
+        let (_, _, nearest) = input.nearest_pair(2).unwrap();
+        let (_, _, farthest) = input.farthest_pair(2).unwrap();
+        let matrix = input.distance_matrix(2);
+        let all: Vec<u128> = (0..9).flat_map(|i| (i+1..9).map(move |j| (i, j)))
+            .map(|(i, j)| matrix[i][j])
+            .collect();
+        assert_eq!(nearest, *all.iter().min().unwrap());
+        assert_eq!(farthest, *all.iter().max().unwrap());
+    }
+
+    #[test]
+    fn test_sum_formula() {
+        let input = Day11::read_input("examples/day11_example1.txt");
+        let formula = input.sum_formula();
+
+        assert_eq!(formula.at(0), 292);
+        assert_eq!(formula.at(1), 292);
+        assert_eq!(formula.at(2), 374);
+        assert_eq!(formula.at(10), 1030);
+        assert_eq!(formula.at(100), 8410);
+        assert_eq!(formula.at(1_000_000_000_000), 292 + 82 * 999_999_999_999);
+    }
+
     #[test]
     fn test_part1() {
         let d = Day11::new("examples/day11_example1.txt");
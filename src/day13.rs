@@ -2,6 +2,29 @@ use std::{fs::File, io::{BufReader, BufRead}};
 
 use crate::day::{Day, Answer};
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Axis {
+    Vertical,       // Mirror between two columns
+    Horizontal,     // Mirror between two rows
+}
+
+// A mirror line at `pos` columns from the left (vertical) or rows from the top (horizontal).
+#[derive(Debug, PartialEq, Eq)]
+struct Mirror {
+    axis: Axis,
+    pos: usize,
+    smudges: Vec<(usize, usize)>,   // (row, col) of cells to flip
+}
+
+impl Mirror {
+    fn summary(&self) -> usize {
+        match self.axis {
+            Axis::Vertical => self.pos,
+            Axis::Horizontal => 100*self.pos,
+        }
+    }
+}
+
 struct Pattern {
     width: usize,
     height: usize,
@@ -26,85 +49,90 @@ impl Pattern {
         Pattern { width, height, rows: new_rows }
     }
 
-    fn test_vert_reflect(&self, pos: usize, smudges: usize) -> bool {
-        // println!("Test vert reflect on w{} x h{} at pos:{}", self.width, self.height, pos);
-
-        if pos < 1 || pos >= self.width {
-            return false;
-        }
-
-        let mut mismatches = 0;
+    // Cells left of (or above) the mirror that differ from their reflection.
+    // Flipping each of these cells makes the mirror exact.
+    fn mismatches(&self, axis: Axis, pos: usize) -> Vec<(usize, usize)> {
+        let mut mismatches: Vec<(usize, usize)> = Vec::new();
 
-        let cols_to_check = if pos <= self.width/2 {
-            pos
-        }
-        else {
-            self.width-pos
-        };
-
-        for n in 0..cols_to_check {
-            // println!("pos: {pos}, n: {n}");
-
-            let right_col = pos+n;
-            let left_col = pos-n-1;
-            for y in 0..self.height {
-                if self.rows[y][left_col] != self.rows[y][right_col] {
-                    // found a mismatch
-                    mismatches += 1;
+        match axis {
+            Axis::Vertical => {
+                if pos < 1 || pos >= self.width {
+                    return mismatches;
+                }
+                let cols_to_check = pos.min(self.width-pos);
+                for n in 0..cols_to_check {
+                    let right_col = pos+n;
+                    let left_col = pos-n-1;
+                    for y in 0..self.height {
+                        if self.rows[y][left_col] != self.rows[y][right_col] {
+                            mismatches.push((y, left_col));
+                        }
+                    }
+                }
+            }
+            Axis::Horizontal => {
+                if pos < 1 || pos >= self.height {
+                    return mismatches;
+                }
+                let rows_to_check = pos.min(self.height-pos);
+                for n in 0..rows_to_check {
+                    let bott_row = pos+n;
+                    let top_row = pos-1-n;
+                    for x in 0..self.width {
+                        if self.rows[bott_row][x] != self.rows[top_row][x] {
+                            mismatches.push((top_row, x));
+                        }
+                    }
                 }
             }
         }
 
-        // This is a reflection if the number of mismatches equals the number of smudges.
-        mismatches == smudges
+        mismatches
     }
 
-    fn test_hor_reflect(&self, pos: usize, smudges: usize) -> bool {
-        if pos < 1 || pos >= self.height {
-            return false;
-        }
-
-        let mut mismatches = 0;
+    // All mirror lines with exactly `smudges` smudges, vertical ones first.
+    fn mirrors(&self, smudges: usize) -> Vec<Mirror> {
+        let vertical = (1..self.width).map(|pos| (Axis::Vertical, pos));
+        let horizontal = (1..self.height).map(|pos| (Axis::Horizontal, pos));
 
-        let rows_to_check = if pos <= self.height/2 {
-            pos
-        }
-        else {
-            self.height-pos
-        };
-
-        for n in 0..rows_to_check {
-            let bott_row = pos+n;
-            let top_row = pos-1-n;
-            for x in 0..self.width {
-                if self.rows[bott_row][x] != self.rows[top_row][x] {
-                    // found a mismatch
-                    mismatches += 1;
-                }
-            }
-        }
-
-        // A reflection is found if mismatches == expected smudges
-        mismatches == smudges
+        vertical.chain(horizontal)
+            .map(|(axis, pos)| Mirror { axis, pos, smudges: self.mismatches(axis, pos) })
+            .filter(|mirror| mirror.smudges.len() == smudges)
+            .collect()
     }
 
     fn reflection(&self, smudges: usize) -> usize {
-        // Look for vertical reflection
-        for pos in 1..=self.width-1 {
-            if self.test_vert_reflect(pos, smudges) {
-                return pos;
-            }
+        match self.mirrors(smudges).first() {
+            Some(mirror) => mirror.summary(),
+            None => panic!("No reflections found."),
         }
+    }
 
-        // Look for horizontal reflection
-        for pos in 1..=self.height-1 {
-            if self.test_hor_reflect(pos, smudges) {
-                return 100*pos;
+    // Draws the pattern with the mirror line ('|' or '-') and the smudges ('*').
+    #[cfg(test)]
+    fn render(&self, mirror: &Mirror) -> String {
+        let mut s = String::new();
+
+        for y in 0..self.height {
+            if mirror.axis == Axis::Horizontal && y == mirror.pos {
+                s += &"-".repeat(self.width);
+                s.push('\n');
+            }
+            for x in 0..self.width {
+                if mirror.axis == Axis::Vertical && x == mirror.pos {
+                    s.push('|');
+                }
+                if mirror.smudges.contains(&(y, x)) {
+                    s.push('*');
+                }
+                else {
+                    s.push(self.rows[y][x]);
+                }
             }
+            s.push('\n');
         }
 
-        // No reflections found.
-        panic!();
+        s
     }
 }
 
@@ -146,6 +174,11 @@ impl Input {
         Input { patterns }
     }
 
+    #[cfg(test)]
+    fn mirrors(&self, smudges: usize) -> Vec<Vec<Mirror>> {
+        self.patterns.iter().map(|p| p.mirrors(smudges)).collect()
+    }
+
     fn sum_reflections(&self, smudges: usize) -> usize {
         self.patterns.iter().map(|p| p.reflection(smudges)).sum()
     }
@@ -179,7 +212,7 @@ impl<'a> Day for Day13<'a> {
 mod test {
     use crate::{day13::Day13, day::{Day, Answer}};
 
-    use super::Input;
+    use super::{Input, Mirror, Axis};
 
     #[test]
     fn test_input() {
@@ -194,30 +227,58 @@ mod test {
     #[test]
     fn test_reflection() {
         let input = Input::read("examples/day13_example1.txt");
-        assert_eq!(input.patterns[0].test_vert_reflect(5, 0), true);
-        assert_eq!(input.patterns[0].test_vert_reflect(4, 0), false);
-        assert_eq!(input.patterns[0].test_vert_reflect(6, 0), false);
+        assert_eq!(input.patterns[0].mismatches(Axis::Vertical, 5).len(), 0);
+        assert_ne!(input.patterns[0].mismatches(Axis::Vertical, 4).len(), 0);
+        assert_ne!(input.patterns[0].mismatches(Axis::Vertical, 6).len(), 0);
         assert_eq!(input.patterns[0].reflection(0), 5);       
 
-        assert_eq!(input.patterns[1].test_hor_reflect(4, 0), true);
-        assert_eq!(input.patterns[1].test_hor_reflect(5, 0), false);
-        assert_eq!(input.patterns[1].test_hor_reflect(3, 0), false);
+        assert_eq!(input.patterns[1].mismatches(Axis::Horizontal, 4).len(), 0);
+        assert_ne!(input.patterns[1].mismatches(Axis::Horizontal, 5).len(), 0);
+        assert_ne!(input.patterns[1].mismatches(Axis::Horizontal, 3).len(), 0);
         assert_eq!(input.patterns[1].reflection(0), 400);  
     }
 
     #[test]
     fn test_reflection2() {
         let input = Input::read("examples/day13_example1.txt");
-        assert_eq!(input.patterns[0].test_hor_reflect(3, 1), true);
-        assert_eq!(input.patterns[0].test_hor_reflect(2, 1), false);
-        assert_eq!(input.patterns[0].test_hor_reflect(4, 1), false);
+        assert_eq!(input.patterns[0].mismatches(Axis::Horizontal, 3).len(), 1);
+        assert_ne!(input.patterns[0].mismatches(Axis::Horizontal, 2).len(), 1);
+        assert_ne!(input.patterns[0].mismatches(Axis::Horizontal, 4).len(), 1);
         assert_eq!(input.patterns[0].reflection(1), 300);       
 
-        assert_eq!(input.patterns[1].test_hor_reflect(1, 1), true);
-        assert_eq!(input.patterns[1].test_hor_reflect(2, 1), false);
+        assert_eq!(input.patterns[1].mismatches(Axis::Horizontal, 1).len(), 1);
+        assert_ne!(input.patterns[1].mismatches(Axis::Horizontal, 2).len(), 1);
         assert_eq!(input.patterns[1].reflection(1), 100);  
     }
 
+    #[test]
+    fn test_mirrors() {
+        let input = Input::read("examples/day13_example1.txt");
+        let mirrors = input.mirrors(0);
+        assert_eq!(mirrors[0], vec![Mirror { axis: Axis::Vertical, pos: 5, smudges: vec![] }]);
+        assert_eq!(mirrors[1], vec![Mirror { axis: Axis::Horizontal, pos: 4, smudges: vec![] }]);
+
+        let mirrors = input.mirrors(1);
+        assert_eq!(mirrors[0], vec![Mirror { axis: Axis::Horizontal, pos: 3, smudges: vec![(0, 0)] }]);
+        assert_eq!(mirrors[1], vec![Mirror { axis: Axis::Horizontal, pos: 1, smudges: vec![(0, 4)] }]);
+    }
+
+    #[test]
+    fn test_render() {
+        let input = Input::read("examples/day13_example1.txt");
+
+        let mirror = &input.patterns[0].mirrors(0)[0];
+        let rendered = input.patterns[0].render(mirror);
+        assert_eq!(rendered.lines().next(), Some("#.##.|.##."));
+
+        let mirror = &input.patterns[0].mirrors(1)[0];
+        let rendered = input.patterns[0].render(mirror);
+        let rendered: Vec<&str> = rendered.lines().collect();
+        assert_eq!(rendered[0], "*.##..##.");
+        assert_eq!(rendered[3], "---------");
+        assert_eq!(rendered.len(), 8);
+    }
+
     #[test]
     fn test_sum_reflection() {
         let input = Input::read("examples/day13_example1.txt");
@@ -1,15 +1,53 @@
-use std::{io::{BufReader, BufRead}, fs::File, fmt::{Formatter, Error}, fmt::Debug, collections::{HashMap, hash_map::DefaultHasher}, hash::{Hash, Hasher}};
+use std::{io::{BufReader, BufRead}, fs::File, fmt::{Formatter, Error}, fmt::Debug};
 
 use crate::day::{Day, Answer};
+use crate::cycle;
 
-#[derive(PartialEq, Eq, Debug, Hash)]
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
 enum Occupation {
     Empty,
     FixedRock,
     MovableRock,
 }
 
-#[derive(Hash)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Tilt {
+    North,
+    West,
+    South,
+    East,
+}
+
+// Tilt programs other than the spin cycle only come up in the tests.
+#[cfg(test)]
+impl Tilt {
+    fn from_char(c: char) -> Option<Tilt> {
+        match c {
+            'N' => Some(Tilt::North),
+            'W' => Some(Tilt::West),
+            'S' => Some(Tilt::South),
+            'E' => Some(Tilt::East),
+            _ => None,
+        }
+    }
+
+    // Parses a tilt program such as "N,E,N,W".
+    fn parse_program(s: &str) -> Option<Vec<Tilt>> {
+        s.split(',')
+            .map(|step| {
+                let mut chars = step.trim().chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Tilt::from_char(c),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+const SPIN_CYCLE: [Tilt; 4] = [Tilt::North, Tilt::West, Tilt::South, Tilt::East];
+
+#[derive(PartialEq, Eq, Hash, Clone)]
 struct Table {
     cells: Vec<Vec<Occupation>>,
 }
@@ -103,81 +141,71 @@ impl Table {
         }
     }
 
-    fn spin_cycle(&mut self) {
-        self.tilt_north();
-        self.tilt_west();
-        self.tilt_south();
-        self.tilt_east();
+    fn tilt(&mut self, dir: Tilt) {
+        match dir {
+            Tilt::North => self.tilt_north(),
+            Tilt::West => self.tilt_west(),
+            Tilt::South => self.tilt_south(),
+            Tilt::East => self.tilt_east(),
+        }
     }
 
-    fn spin_multiple(&mut self, limit: usize) {
-        let mut hashes: HashMap<u64, usize> = HashMap::new();
-
-        let mut hasher = DefaultHasher::new();
-        let mut count = 0;
-
-        // generate hash, store this value with count 0.
-        self.hash(&mut hasher);
-        let hash_value = hasher.finish();
-        hashes.insert(hash_value, count);
-
-        // search for the repeating period in the following loop, until it's found.
-        let mut searching = true;
-
-        while count < limit {
-            self.spin_cycle();
-            count += 1;
-
-            let mut hasher = DefaultHasher::new();
-            self.hash(&mut hasher);
-            let hash_value = hasher.finish();
-
-
-
-            if searching && hashes.contains_key(&hash_value) {
-                // We found a repeating cycle.  Fast forward N cycles, where N
-                // is a multiple of the period and count+N*period <= limit
-                let period = count - hashes.get(&hash_value).unwrap();
-
-                let ff = ((limit - count) / period) * period;
-                // println!("At count:{count}, found period:{period}, fast forward: {ff}");
-
-                count += ff;
-
-                // No longer searching for the period
-                searching = false;
-            }
+    fn run(&mut self, program: &[Tilt]) {
+        for dir in program {
+            self.tilt(*dir);
+        }
+    }
 
-            hashes.insert(hash_value, count);
+    // Runs the program repeatedly until a table state repeats, keeping every
+    // state.  States are compared in full, so there's no chance of a hash collision.
+    #[cfg(test)]
+    fn find_cycle(&self, program: &[Tilt]) -> cycle::CycleHistory<Table> {
+        cycle::with_history(self.clone(), |table| {
+            let mut next = table.clone();
+            next.run(program);
+            next
+        })
+    }
 
-            // println!("{:?}", self);
-            // println!("Count: {count} Hash value: {hash_value}, Load: {}", self.north_load());
-            // println!();
-        }
+    // Only the start needs keeping: find the cycle, then rerun the spins up
+    // to the equivalent step inside the first loop.
+    fn spin_multiple(&mut self, limit: usize) {
+        let spin = |table: &Table| {
+            let mut next = table.clone();
+            next.run(&SPIN_CYCLE);
+            next
+        };
+
+        let cycle = cycle::brent(self, spin);
+        *self = cycle.state_at(self, spin, limit);
     }
 
-    fn north_load(&self) -> usize {
+    // Total load on the support beams on the given side of the table.
+    // Each rock's load is its distance from the opposite edge.
+    fn load(&self, side: Tilt) -> usize {
+        let rows = self.cells.len();
+        let cols = self.cells[0].len();
         let mut total_load = 0;
 
-        for row in 0..self.cells.len() {
-            let single_load = self.cells.len() - row;
-            for col in 0..self.cells[0].len() {
-                total_load += match self.cells[row][col] {
-                    Occupation::MovableRock => {
-                        single_load
-                    }
-                    Occupation::FixedRock => {
-                        0
-                    }
-                    Occupation::Empty => {
-                        0
-                    }
+        for row in 0..rows {
+            for col in 0..cols {
+                if self.cells[row][col] == Occupation::MovableRock {
+                    total_load += match side {
+                        Tilt::North => rows - row,
+                        Tilt::South => row + 1,
+                        Tilt::West => cols - col,
+                        Tilt::East => col + 1,
+                    };
                 }
             }
         }
 
         total_load
     }
+
+    fn north_load(&self) -> usize {
+        self.load(Tilt::North)
+    }
 }
 
 struct Input {
@@ -244,7 +272,7 @@ impl<'a> Day for Day14<'a> {
 
 #[cfg(test)]
 mod test {
-    use crate::day14::{Occupation, Input};
+    use crate::day14::{Occupation, Input, Tilt, SPIN_CYCLE};
 
     #[test]
     fn test_input() {
@@ -273,7 +301,7 @@ mod test {
     fn test_spin() {
         let mut input = Input::read("examples/day14_example1.txt");
         for _ in 0..3 {
-            input.table.spin_cycle();
+            input.table.run(&SPIN_CYCLE);
             // println!("Spin: ");
             // println!("{:?}", input.table);
             // println!("Load: {}", input.table.north_load());
@@ -300,4 +328,59 @@ mod test {
 
         assert_eq!(total_load, 64);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_program() {
+        assert_eq!(Tilt::parse_program("N,E,N,W"),
+            Some(vec![Tilt::North, Tilt::East, Tilt::North, Tilt::West]));
+        assert_eq!(Tilt::parse_program("N,X"), None);
+        assert_eq!(Tilt::parse_program("NE"), None);
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let input = Input::read("examples/day14_example1.txt");
+        let cycle = input.table.find_cycle(&SPIN_CYCLE);
+
+        assert_eq!(cycle.cycle.prefix, 3);
+        assert_eq!(cycle.cycle.period, 7);
+        assert_eq!(cycle.state_at(3).north_load(), 69);
+        assert_eq!(cycle.state_at(1000000000).north_load(), 64);
+
+        // Every state in the loop matches the one a period later.
+        for step in 3..20 {
+            assert!(cycle.state_at(step) == cycle.state_at(step + cycle.cycle.period));
+        }
+    }
+
+    #[test]
+    fn test_custom_program() {
+        let input = Input::read("examples/day14_example1.txt");
+        let program = Tilt::parse_program("N,E,N,W").unwrap();
+        let cycle = input.table.find_cycle(&program);
+
+        let mut table = input.table.clone();
+        for step in 0..50 {
+            assert!(*cycle.state_at(step) == table);
+            table.run(&program);
+        }
+    }
+
+    #[test]
+    fn test_load_directions() {
+        let mut input = Input::read("examples/day14_example1.txt");
+        input.table.tilt_north();
+        assert_eq!(input.table.load(Tilt::North), 136);
+
+        // Flipping the table upside down swaps north and south loads.
+        let mut flipped = input.table.clone();
+        flipped.cells.reverse();
+        assert_eq!(flipped.load(Tilt::South), 136);
+
+        let rocks = input.table.cells.iter().flatten()
+            .filter(|c| **c == Occupation::MovableRock)
+            .count();
+        let width = input.table.cells[0].len();
+        assert_eq!(input.table.load(Tilt::West) + input.table.load(Tilt::East), rocks * (width + 1));
+    }
+}
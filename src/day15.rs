@@ -1,4 +1,4 @@
-use std::{fs::File, io::{BufReader, BufRead}};
+use std::{fs::File, io::{BufReader, BufRead}, fmt::{Display, Formatter, Error}};
 
 use crate::day::{Day, Answer};
 use lazy_static::lazy_static;
@@ -26,6 +26,7 @@ impl Instruction {
         Instruction::hash_str(&self.text)
     }
 
+    #[cfg(test)]
     pub fn box_no(&self) -> usize {
         let mut n = 0;
         for c in self.text.chars() {
@@ -76,6 +77,83 @@ impl Instruction {
     }
 }
 
+#[derive(Clone, PartialEq, Debug)]
+struct Lens {
+    label: String,
+    focal_length: usize,
+}
+
+// The 256 boxes.  Each box holds lenses in slot order.
+#[derive(Clone)]
+struct Boxes {
+    boxes: Vec<Vec<Lens>>,
+}
+
+impl Boxes {
+    fn new() -> Boxes {
+        Boxes { boxes: vec![Vec::new(); 256] }
+    }
+
+    fn slot_of(&self, box_no: usize, label: &str) -> Option<usize> {
+        self.boxes[box_no].iter().position(|lens| lens.label == label)
+    }
+
+    // Add lens with this label, replacing existing or at end
+    fn insert(&mut self, label: &str, focal_length: usize) {
+        let box_no = Instruction::hash_str(label) as usize;
+        match self.slot_of(box_no, label) {
+            Some(slot) => self.boxes[box_no][slot].focal_length = focal_length,
+            None => self.boxes[box_no].push(Lens { label: label.to_string(), focal_length }),
+        }
+    }
+
+    // Remove lens with this label (if any)
+    fn remove(&mut self, label: &str) {
+        let box_no = Instruction::hash_str(label) as usize;
+        if let Some(slot) = self.slot_of(box_no, label) {
+            self.boxes[box_no].remove(slot);
+        }
+    }
+
+    fn apply(&mut self, i: &Instruction) {
+        match i.insert_pos() {
+            Some(focal_length) => self.insert(i.label(), focal_length),
+            None => self.remove(i.label()),
+        }
+    }
+
+    // (label, power) for every lens, in box and slot order.
+    fn contributions(&self) -> Vec<(&str, usize)> {
+        self.boxes.iter().enumerate()
+            .flat_map(|(box_no, lenses)| {
+                lenses.iter().enumerate()
+                    .map(move |(slot, lens)| (lens.label.as_str(), (1+box_no) * (1+slot) * lens.focal_length))
+            })
+            .collect()
+    }
+
+    fn focusing_power(&self) -> usize {
+        self.contributions().iter().map(|(_label, power)| power).sum()
+    }
+}
+
+// Non-empty boxes, one per line: "Box 3: [pc 4] [ot 9]"
+impl Display for Boxes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        for (box_no, lenses) in self.boxes.iter().enumerate() {
+            if !lenses.is_empty() {
+                write!(f, "Box {box_no}:")?;
+                for lens in lenses {
+                    write!(f, " [{} {}]", lens.label, lens.focal_length)?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 struct Input {
     instructions: Vec<Instruction>,
 }
@@ -113,61 +191,18 @@ impl Input {
         self.instructions.iter().map(|i| i.hash() as usize).sum()
     }
 
-    fn focusing_power(&self) -> usize {
-        // Each box is a vector of (label, focal length)
-        const EMPTY_BOX: Vec<(&str, usize)> = vec![];
-        let mut boxes: [Vec<(&str, usize)>; 256] = [EMPTY_BOX; 256];
-
-        // Go though instructions, moving lenses around
-        for i in &self.instructions {
-            let box_no = i.box_no();
-            let label = i.label();
-            match i.insert_pos() {
-                Some(focal_length) => {
-                    // Add lens with this label, replacing existing or at end
-                    let mut index = 0;
-                    for (label, _fl) in &boxes[box_no] {
-                        if *label == i.label() { 
-                            break; 
-                        }
-                        index += 1;
-                    }
-                    if index < boxes[box_no].len() {
-                        // replace entry at index
-                        boxes[box_no][index] = (label, focal_length);
-                    }
-                    else {
-                        // append entry to the end
-                        boxes[box_no].push( (label, focal_length) );
-                    }
-                }
-                None => {
-                    // Remove lens with this label (if any)
-                    let mut index = 0;
-                    for (label, _fl) in &boxes[box_no] {
-                        if *label == i.label() { 
-                            break; 
-                        }
-                        index += 1;
-                    }
-                    if index < boxes[box_no].len() {
-                        // remove the element at index
-                        boxes[box_no].remove(index);
-                    }
-                }
-            }
+    // State of the boxes after the first n instructions.
+    fn boxes_after(&self, n: usize) -> Boxes {
+        let mut boxes = Boxes::new();
+        for i in self.instructions.iter().take(n) {
+            boxes.apply(i);
         }
 
-        // Add up components of processing power
-        let mut power = 0;
-        for box_no in 0..boxes.len() {
-            for slot in 0..boxes[box_no].len() {
-                
-                power += (1+box_no) * (1+slot) * boxes[box_no][slot].1;
-            }
-        }
+        boxes
+    }
 
-        power
+    fn focusing_power(&self) -> usize {
+        self.boxes_after(self.instructions.len()).focusing_power()
     }
 }
 
@@ -197,7 +232,7 @@ impl<'a> Day for Day15<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{day15::{Input, Day15, Instruction}, day::{Day, Answer}};
+    use crate::{day15::{Input, Day15, Instruction, Boxes}, day::{Day, Answer}};
 
     #[test]
     fn test_hash() {
@@ -270,6 +305,41 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_boxes_after() {
+        let input = Input::read("examples/day15_example1.txt");
+
+        assert_eq!(input.boxes_after(0).to_string(), "");
+        assert_eq!(input.boxes_after(1).to_string(), "Box 0: [rn 1]\n");
+        assert_eq!(input.boxes_after(2).to_string(), "Box 0: [rn 1]\n");
+        assert_eq!(input.boxes_after(6).to_string(), "Box 0: [rn 1] [cm 2]\nBox 3: [pc 4]\n");
+        assert_eq!(input.boxes_after(11).to_string(), "Box 0: [rn 1] [cm 2]\nBox 3: [ot 7] [ab 5] [pc 6]\n");
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut boxes = Boxes::new();
+        boxes.insert("qp", 3);
+        boxes.insert("cm", 2);
+        boxes.insert("qp", 4);
+        assert_eq!(boxes.to_string(), "Box 0: [cm 2]\nBox 1: [qp 4]\n");
+
+        boxes.remove("qp");
+        boxes.remove("zz");
+        assert_eq!(boxes.to_string(), "Box 0: [cm 2]\n");
+    }
+
+    #[test]
+    fn test_contributions() {
+        let input = Input::read("examples/day15_example1.txt");
+        let boxes = input.boxes_after(11);
+
+        assert_eq!(boxes.contributions(), vec![
+            ("rn", 1), ("cm", 4), ("ot", 28), ("ab", 40), ("pc", 72)
+        ]);
+        assert_eq!(boxes.focusing_power(), 145);
+    }
+
     #[test]
     fn test_part1() {
         let d = Day15::new("examples/day15_example1.txt");
@@ -1,6 +1,9 @@
-use std::{fs::File, io::{BufReader, BufRead}, collections::{HashSet, HashMap}};
+use std::{fs::File, io::{BufReader, BufRead}};
 
 use crate::day::{Day, Answer};
+use crate::bitset::BitSet;
+#[cfg(test)]
+use rgb::RGB8;
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 enum Direction {
@@ -10,6 +13,48 @@ enum Direction {
     Left,
 }
 
+const ALL_DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Right, Direction::Down, Direction::Left];
+
+impl Direction {
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    fn step(&self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+
+    // A ray with this direction, encountering elt, produces rays in these directions.
+    fn through(&self, elt: GridElt) -> &'static [Direction] {
+        match (elt, self) {
+            (GridElt::Empty, _) => &ALL_DIRECTIONS[*self as usize..=*self as usize],
+
+            (GridElt::ReflectSlash, Direction::Up) => &[Direction::Right],
+            (GridElt::ReflectSlash, Direction::Down) => &[Direction::Left],
+            (GridElt::ReflectSlash, Direction::Left) => &[Direction::Down],
+            (GridElt::ReflectSlash, Direction::Right) => &[Direction::Up],
+
+            (GridElt::ReflectBackslash, Direction::Up) => &[Direction::Left],
+            (GridElt::ReflectBackslash, Direction::Down) => &[Direction::Right],
+            (GridElt::ReflectBackslash, Direction::Left) => &[Direction::Up],
+            (GridElt::ReflectBackslash, Direction::Right) => &[Direction::Down],
+
+            (GridElt::SplitUpDown, Direction::Up) => &[Direction::Up],
+            (GridElt::SplitUpDown, Direction::Down) => &[Direction::Down],
+            (GridElt::SplitUpDown, _) => &[Direction::Up, Direction::Down],
+
+            (GridElt::SplitLeftRight, Direction::Left) => &[Direction::Left],
+            (GridElt::SplitLeftRight, Direction::Right) => &[Direction::Right],
+            (GridElt::SplitLeftRight, _) => &[Direction::Left, Direction::Right],
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
 enum GridElt {
     Empty,
@@ -54,101 +99,135 @@ impl Input {
         Input { grid }
     }
 
+    fn neighbor(&self, row: usize, col: usize, dir: Direction) -> Option<(usize, usize)> {
+        let (d_row, d_col) = dir.step();
+        let new_row = row.checked_add_signed(d_row)?;
+        let new_col = col.checked_add_signed(d_col)?;
+
+        if new_row < self.grid.len() && new_col < self.grid[0].len() {
+            Some((new_row, new_col))
+        }
+        else {
+            None
+        }
+    }
+
+    // Beam-by-beam search.  For a single start this is cheaper than building the engine.
     pub fn energize(&self, dir: Direction, row: usize, col: usize) -> usize {
-        // A ray with <Direction>, encounting <GridElt> will produce a vector of rays,
-        // with new_direction <Direction>.
-        let propagation: HashMap<(Direction, GridElt), Vec<Direction>> = HashMap::from([
-            // Empty grid cell
-            ( (Direction::Up, GridElt::Empty), vec![Direction::Up] ),
-            ( (Direction::Down, GridElt::Empty), vec![Direction::Down] ),
-            ( (Direction::Left, GridElt::Empty), vec![Direction::Left] ),
-            ( (Direction::Right, GridElt::Empty), vec![Direction::Right] ),
-
-            // ReflectSlash grid cell
-            ( (Direction::Up, GridElt::ReflectSlash), vec![Direction::Right] ),
-            ( (Direction::Down, GridElt::ReflectSlash), vec![Direction::Left] ),
-            ( (Direction::Left, GridElt::ReflectSlash), vec![Direction::Down] ),
-            ( (Direction::Right, GridElt::ReflectSlash), vec![Direction::Up] ),
-
-            // ReflectBackslash grid cell
-            ( (Direction::Up, GridElt::ReflectBackslash), vec![Direction::Left] ),
-            ( (Direction::Down, GridElt::ReflectBackslash), vec![Direction::Right] ),
-            ( (Direction::Left, GridElt::ReflectBackslash), vec![Direction::Up] ),
-            ( (Direction::Right, GridElt::ReflectBackslash), vec![Direction::Down] ),
-
-            // SplitUpDown grid cell
-            ( (Direction::Up, GridElt::SplitUpDown), vec![Direction::Up] ),
-            ( (Direction::Down, GridElt::SplitUpDown), vec![Direction::Down] ),
-            ( (Direction::Left, GridElt::SplitUpDown), vec![Direction::Up, Direction::Down] ),
-            ( (Direction::Right, GridElt::SplitUpDown), vec![Direction::Down, Direction::Up] ),
-
-            // SplitLeftRight grid cell
-            ( (Direction::Up, GridElt::SplitLeftRight), vec![Direction::Left, Direction::Right] ),
-            ( (Direction::Down, GridElt::SplitLeftRight), vec![Direction::Left, Direction::Right] ),
-            ( (Direction::Left, GridElt::SplitLeftRight), vec![Direction::Left] ),
-            ( (Direction::Right, GridElt::SplitLeftRight), vec![Direction::Right] ),
-        ]);
-
-        let movement: HashMap<Direction, (isize, isize)> = HashMap::from([
-            (Direction::Up, (-1, 0)),
-            (Direction::Down, (1, 0)),
-            (Direction::Left, (0, -1)),
-            (Direction::Right, (0, 1)),
-        ]);
-
-        let rows = self.grid.len() as isize;
-        let cols = self.grid[0].len() as isize;
-
-        // These are beams to chase down: (row, col, direction)
-        let mut to_do: Vec<(usize, usize, Direction)> = Vec::new();
-
-        // beams that have already been processed
-        let mut done: HashSet<(usize, usize, Direction)> = HashSet::new();
-
-        // energized cells
-        let mut energized: HashSet<(usize, usize)> = HashSet::new();
-
-        to_do.push( (row, col, dir) );
-        done.insert( (row, col, dir) );
-        energized.insert( (row, col) );
-
-        while to_do.len() > 0 {
-            let (row, col, dir) = to_do.pop().unwrap();
-
-            // propagate
-            // println!("{} : Propagating {dir:?} into ({row},{col}), {:?}", to_do.len()+1, self.grid[row][col]);
-            let propagated = propagation.get( &(dir, self.grid[row][col]) ).unwrap();
-            for dir in propagated {
-                let (d_row, d_col) = movement.get(dir).unwrap();
-                let new_col = col as isize + d_col;
-                let new_row = row as isize + d_row;
-
-                // println!("  ({row}, {col}), go {dir:?}, to ({new_row}, {new_col})");
-
-                if new_col < 0 || new_col >= cols ||
-                   new_row < 0 || new_row >= rows {
-                    // this propagation went off the edge
-                    // println!("    off the map.");
-                    continue;
+        let cols = self.grid[0].len();
+
+        // For each cell, a bit for each direction a beam has entered it in
+        let mut seen: Vec<u8> = vec![0; self.grid.len()*cols];
+        seen[row*cols + col] |= 1 << dir.index();
+        let mut to_do: Vec<(usize, usize, Direction)> = vec![(row, col, dir)];
+
+        while let Some((row, col, dir)) = to_do.pop() {
+            for out_dir in dir.through(self.grid[row][col]) {
+                if let Some((new_row, new_col)) = self.neighbor(row, col, *out_dir) {
+                    let mask = &mut seen[new_row*cols + new_col];
+                    if *mask & (1 << out_dir.index()) == 0 {
+                        *mask |= 1 << out_dir.index();
+                        to_do.push((new_row, new_col, *out_dir));
+                    }
                 }
+            }
+        }
 
-                let new_col = new_col as usize;
-                let new_row = new_row as usize;
+        seen.iter().filter(|mask| **mask != 0).count()
+    }
 
-                if !done.contains(&(new_row, new_col, *dir)) {
-                    // we need to explore this further
-                    to_do.push((new_row, new_col, *dir));
-                    done.insert((new_row, new_col, *dir));
-                    energized.insert((new_row, new_col));
+    // Follows the beams one propagation step at a time, keeping their directions.
+    #[cfg(test)]
+    fn trace_beams(&self, dir: Direction, row: usize, col: usize) -> BeamTrace {
+        let cols = self.grid[0].len();
+        let mut trace = BeamTrace::new(self.grid.len(), cols);
+
+        let mut frontier: Vec<(usize, usize, Direction)> = vec![(row, col, dir)];
+        trace.dirs[row*cols + col] |= 1 << dir.index();
+
+        while !frontier.is_empty() {
+            let mut next_frontier: Vec<(usize, usize, Direction)> = Vec::new();
+            for (row, col, dir) in &frontier {
+                for out_dir in dir.through(self.grid[*row][*col]) {
+                    if let Some((new_row, new_col)) = self.neighbor(*row, *col, *out_dir) {
+                        let mask = &mut trace.dirs[new_row*cols + new_col];
+                        if *mask & (1 << out_dir.index()) == 0 {
+                            *mask |= 1 << out_dir.index();
+                            next_frontier.push((new_row, new_col, *out_dir));
+                        }
+                    }
                 }
-                else {
-                    // println!("    been there.");
+            }
+
+            trace.steps.push(frontier);
+            frontier = next_frontier;
+        }
+
+        trace
+    }
+
+    // The contraption with beams drawn on empty cells, as in the puzzle:
+    // an arrow for one beam, or the number of beams passing through.
+    #[cfg(test)]
+    fn render(&self, trace: &BeamTrace) -> String {
+        let mut s = String::new();
+
+        for (row, grid_line) in self.grid.iter().enumerate() {
+            for (col, elt) in grid_line.iter().enumerate() {
+                let dirs = trace.directions(row, col);
+                s.push(match (elt, dirs.len()) {
+                    (GridElt::Empty, 0) => '.',
+                    (GridElt::Empty, 1) => match dirs[0] {
+                        Direction::Up => '^',
+                        Direction::Right => '>',
+                        Direction::Down => 'v',
+                        Direction::Left => '<',
+                    },
+                    (GridElt::Empty, n) => char::from_digit(n as u32, 10).unwrap(),
+                    (GridElt::ReflectBackslash, _) => '\\',
+                    (GridElt::ReflectSlash, _) => '/',
+                    (GridElt::SplitUpDown, _) => '|',
+                    (GridElt::SplitLeftRight, _) => '-',
+                });
+            }
+            s.push('\n');
+        }
+
+        s
+    }
+
+    // A binary PPM image with `scale` pixels per cell.
+    #[cfg(test)]
+    fn render_ppm(&self, trace: &BeamTrace, scale: usize) -> Vec<u8> {
+        const BACKGROUND: RGB8 = RGB8 { r: 16, g: 16, b: 32 };
+        const DEVICE: RGB8 = RGB8 { r: 160, g: 160, b: 160 };
+        const BEAM: RGB8 = RGB8 { r: 255, g: 200, b: 40 };
+        const LIT_DEVICE: RGB8 = RGB8 { r: 255, g: 255, b: 255 };
+
+        let width = trace.cols * scale;
+        let height = trace.rows * scale;
+        let mut image: Vec<u8> = format!("P6\n{width} {height}\n255\n").into_bytes();
+
+        for row in 0..trace.rows {
+            let line: Vec<RGB8> = (0..trace.cols)
+                .map(|col| match (self.grid[row][col], trace.is_energized(row, col)) {
+                    (GridElt::Empty, false) => BACKGROUND,
+                    (GridElt::Empty, true) => BEAM,
+                    (_, false) => DEVICE,
+                    (_, true) => LIT_DEVICE,
+                })
+                .collect();
+
+            for _ in 0..scale {
+                for pixel in &line {
+                    for _ in 0..scale {
+                        image.extend([pixel.r, pixel.g, pixel.b]);
+                    }
                 }
             }
         }
 
-        // return the number of energized cells
-        energized.len()
+        image
     }
 
     fn max_energize(&self) -> usize {
@@ -167,12 +246,336 @@ impl Input {
             options.push( (Direction::Up, last_row, col) );
         }
 
-        options.iter()
-            .map(|(dir, row, col)| self.energize(*dir, *row, *col))
+        // All the starts share one engine.
+        BeamEngine::new(self).energize_all(&options)
+            .into_iter()
             .max().unwrap()
     }
 }
 
+// Beams found by trace_beams.  dirs holds a bit for each direction a beam
+// passes through a cell.  steps[n] holds the beams that first appear at step n.
+#[cfg(test)]
+#[derive(Clone)]
+struct BeamTrace {
+    rows: usize,
+    cols: usize,
+    dirs: Vec<u8>,
+    steps: Vec<Vec<(usize, usize, Direction)>>,
+}
+
+#[cfg(test)]
+impl BeamTrace {
+    fn new(rows: usize, cols: usize) -> BeamTrace {
+        BeamTrace { rows, cols, dirs: vec![0; rows*cols], steps: Vec::new() }
+    }
+
+    fn directions(&self, row: usize, col: usize) -> Vec<Direction> {
+        let mask = self.dirs[row*self.cols + col];
+        ALL_DIRECTIONS.iter()
+            .filter(|dir| mask & (1 << dir.index()) != 0)
+            .copied()
+            .collect()
+    }
+
+    fn is_energized(&self, row: usize, col: usize) -> bool {
+        self.dirs[row*self.cols + col] != 0
+    }
+
+    fn energized(&self) -> Vec<(usize, usize)> {
+        (0..self.rows)
+            .flat_map(|row| (0..self.cols).map(move |col| (row, col)))
+            .filter(|(row, col)| self.is_energized(*row, *col))
+            .collect()
+    }
+
+    // The trace as it was after the first n propagation steps, for animation.
+    fn frame(&self, n: usize) -> BeamTrace {
+        let mut frame = BeamTrace::new(self.rows, self.cols);
+        for step in self.steps.iter().take(n) {
+            for (row, col, dir) in step {
+                frame.dirs[row*self.cols + col] |= 1 << dir.index();
+            }
+            frame.steps.push(step.clone());
+        }
+
+        frame
+    }
+}
+
+// A straight run of beam: `len` cells from (row, col) in direction dir, and
+// the beams it turns into when it stops at a mirror or splitter.
+struct Segment {
+    row: usize,
+    col: usize,
+    dir: Direction,
+    len: usize,
+    next: Vec<usize>,
+}
+
+/*
+The beam engine breaks every beam into segments that run from one non-empty
+cell to the next.  A node is a beam leaving a non-empty cell in some
+direction, numbered (non-empty cell index)*4 + direction.  Each node has
+exactly one segment, so the nodes form a graph.
+
+Beams can loop, so the graph is condensed into strongly connected
+components.  Every node in a component lights the same cells, and
+components are found sinks first, so each component's lit cells are its own
+segments plus those of the components it leads to.  Those are worked out
+once and shared by every starting beam.  Only components some start leads
+to get a set of lit cells, and each set is dropped once the last component
+or start that reads it is done, so only the sets still waiting on a reader
+are kept at once.
+*/
+struct BeamEngine<'a> {
+    input: &'a Input,
+    rows: usize,
+    cols: usize,
+
+    // For each cell: index among the non-empty cells, or None
+    elt_index: Vec<Option<usize>>,
+
+    // For each node: the segment of the beam leaving it
+    segments: Vec<Segment>,
+
+    // For each node: the component it's in
+    component: Vec<usize>,
+
+    // For each component: the nodes in it
+    members: Vec<Vec<usize>>,
+}
+
+impl<'a> BeamEngine<'a> {
+    fn new(input: &'a Input) -> BeamEngine<'a> {
+        let rows = input.grid.len();
+        let cols = input.grid[0].len();
+
+        let mut elt_index: Vec<Option<usize>> = vec![None; rows*cols];
+        let mut elts: Vec<(usize, usize)> = Vec::new();
+        for row in 0..rows {
+            for col in 0..cols {
+                if input.grid[row][col] != GridElt::Empty {
+                    elt_index[row*cols + col] = Some(elts.len());
+                    elts.push((row, col));
+                }
+            }
+        }
+
+        let mut engine = BeamEngine { input, rows, cols, elt_index, segments: Vec::new(), component: Vec::new(), members: Vec::new() };
+
+        // Segments for the beams leaving each non-empty cell, in node order.
+        let mut segments: Vec<Segment> = Vec::with_capacity(elts.len()*4);
+        for (row, col) in elts {
+            for dir in ALL_DIRECTIONS {
+                let segment = match input.neighbor(row, col, dir) {
+                    Some((next_row, next_col)) => engine.trace(next_row, next_col, dir),
+                    None => Segment { row, col, dir, len: 0, next: Vec::new() },
+                };
+                segments.push(segment);
+            }
+        }
+
+        let edges: Vec<&[usize]> = segments.iter().map(|s| s.next.as_slice()).collect();
+        let (component, members) = Self::components(&edges);
+
+        engine.segments = segments;
+        engine.component = component;
+        engine.members = members;
+        engine
+    }
+
+    // The number of cells energized by each of the starting beams.
+    fn energize_all(&self, starts: &[(Direction, usize, usize)]) -> Vec<usize> {
+        let starts: Vec<Segment> = starts.iter()
+            .map(|(dir, row, col)| self.trace(*row, *col, *dir))
+            .collect();
+        let num_components = self.members.len();
+
+        // The components some start leads to
+        let mut reachable: Vec<bool> = vec![false; num_components];
+        let mut to_do: Vec<usize> = starts.iter()
+            .flat_map(|start| start.next.iter().map(|node| self.component[*node]))
+            .collect();
+        while let Some(comp) = to_do.pop() {
+            if !reachable[comp] {
+                reachable[comp] = true;
+                for node in &self.members[comp] {
+                    to_do.extend(self.segments[*node].next.iter().map(|next| self.component[*next]));
+                }
+            }
+        }
+
+        // How many times each component's lit cells will be read.
+        let mut readers: Vec<usize> = vec![0; num_components];
+        for comp in (0..num_components).filter(|comp| reachable[*comp]) {
+            for node in &self.members[comp] {
+                for next in &self.segments[*node].next {
+                    if self.component[*next] != comp {
+                        readers[self.component[*next]] += 1;
+                    }
+                }
+            }
+        }
+
+        // Each start is counted as soon as the last component it leads to is done.
+        let mut counts: Vec<usize> = vec![0; starts.len()];
+        let mut ready: Vec<Vec<usize>> = vec![Vec::new(); num_components];
+        for (n, start) in starts.iter().enumerate() {
+            for next in &start.next {
+                readers[self.component[*next]] += 1;
+            }
+            match start.next.iter().map(|next| self.component[*next]).max() {
+                Some(last) => ready[last].push(n),
+                None => {
+                    let mut cells = BitSet::new(self.rows*self.cols);
+                    self.light(start, &mut cells);
+                    counts[n] = cells.count();
+                }
+            }
+        }
+
+        // Components come out sinks first, so successors are always done already.
+        let mut lit: Vec<Option<BitSet>> = vec![None; num_components];
+        for comp in (0..num_components).filter(|comp| reachable[*comp]) {
+            let mut cells = BitSet::new(self.rows*self.cols);
+            for node in &self.members[comp] {
+                self.light(&self.segments[*node], &mut cells);
+                self.read_successors(&self.segments[*node], Some(comp), &mut cells, &mut lit, &mut readers);
+            }
+            lit[comp] = Some(cells);
+
+            for n in &ready[comp] {
+                let mut cells = BitSet::new(self.rows*self.cols);
+                self.light(&starts[*n], &mut cells);
+                self.read_successors(&starts[*n], None, &mut cells, &mut lit, &mut readers);
+                counts[*n] = cells.count();
+            }
+        }
+
+        counts
+    }
+
+    // Adds the cells lit by the components segment leads to, other than its own.
+    // A component's cells are dropped once their last reader is done.
+    fn read_successors(&self, segment: &Segment, own: Option<usize>, cells: &mut BitSet, lit: &mut [Option<BitSet>], readers: &mut [usize]) {
+        for next in &segment.next {
+            let comp = self.component[*next];
+            if Some(comp) != own {
+                cells.union_with(lit[comp].as_ref().unwrap());
+                readers[comp] -= 1;
+                if readers[comp] == 0 {
+                    lit[comp] = None;
+                }
+            }
+        }
+    }
+
+    fn grid_elt(&self, row: usize, col: usize) -> GridElt {
+        self.input.grid[row][col]
+    }
+
+    fn node(&self, row: usize, col: usize, dir: Direction) -> usize {
+        self.elt_index[row*self.cols + col].unwrap()*4 + dir.index()
+    }
+
+    // Follow a beam entering (row, col) until it reaches a non-empty cell or leaves the grid.
+    fn trace(&self, row: usize, col: usize, dir: Direction) -> Segment {
+        let mut len = 0;
+        let mut pos = Some((row, col));
+
+        while let Some((at_row, at_col)) = pos {
+            len += 1;
+
+            let elt = self.grid_elt(at_row, at_col);
+            if elt != GridElt::Empty {
+                let next = dir.through(elt).iter()
+                    .map(|out_dir| self.node(at_row, at_col, *out_dir))
+                    .collect();
+                return Segment { row, col, dir, len, next };
+            }
+
+            pos = self.input.neighbor(at_row, at_col, dir);
+        }
+
+        Segment { row, col, dir, len, next: Vec::new() }
+    }
+
+    fn light(&self, segment: &Segment, cells: &mut BitSet) {
+        let (d_row, d_col) = segment.dir.step();
+        for n in 0..segment.len as isize {
+            let row = (segment.row as isize + n*d_row) as usize;
+            let col = (segment.col as isize + n*d_col) as usize;
+            cells.insert(row*self.cols + col);
+        }
+    }
+
+    // Tarjan's strongly connected components, without recursion.
+    // Returns the component of each node, and the nodes of each component.
+    // Components are numbered in reverse topological order.
+    fn components(edges: &[&[usize]]) -> (Vec<usize>, Vec<Vec<usize>>) {
+        let n = edges.len();
+        let mut index: Vec<usize> = vec![usize::MAX; n];
+        let mut low: Vec<usize> = vec![0; n];
+        let mut on_stack: Vec<bool> = vec![false; n];
+        let mut stack: Vec<usize> = Vec::new();
+        let mut component: Vec<usize> = vec![usize::MAX; n];
+        let mut members: Vec<Vec<usize>> = Vec::new();
+        let mut next_index = 0;
+
+        for root in 0..n {
+            if index[root] != usize::MAX {
+                continue;
+            }
+
+            // (node, position in its edge list)
+            let mut call: Vec<(usize, usize)> = vec![(root, 0)];
+            index[root] = next_index;
+            low[root] = next_index;
+            next_index += 1;
+            stack.push(root);
+            on_stack[root] = true;
+
+            while let Some((v, pos)) = call.pop() {
+                if pos < edges[v].len() {
+                    call.push((v, pos+1));
+                    let w = edges[v][pos];
+                    if index[w] == usize::MAX {
+                        index[w] = next_index;
+                        low[w] = next_index;
+                        next_index += 1;
+                        stack.push(w);
+                        on_stack[w] = true;
+                        call.push((w, 0));
+                    }
+                    else if on_stack[w] {
+                        low[v] = low[v].min(index[w]);
+                    }
+                }
+                else {
+                    if let Some((u, _)) = call.last() {
+                        low[*u] = low[*u].min(low[v]);
+                    }
+                    if low[v] == index[v] {
+                        let mut nodes: Vec<usize> = Vec::new();
+                        while let Some(w) = stack.pop() {
+                            on_stack[w] = false;
+                            component[w] = members.len();
+                            nodes.push(w);
+                            if w == v {
+                                break;
+                            }
+                        }
+                        members.push(nodes);
+                    }
+                }
+            }
+        }
+
+        (component, members)
+    }
+}
+
 pub struct Day16<'a> {
     _input_filename: &'a str,
 }
@@ -199,7 +602,7 @@ impl<'a> Day for Day16<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{day16::{GridElt, Input, Day16, Direction}, day::{Answer, Day}};
+    use crate::{day16::{GridElt, Input, Day16, Direction, BeamEngine}, day::{Answer, Day}};
 
     #[test]
     fn test_input() {
@@ -241,4 +644,90 @@ mod tests {
 
         assert_eq!(d.part2(), Answer::Numeric(51));
     }
+
+    #[test]
+    fn test_trace_beams() {
+        let input = Input::read("examples/day16_example1.txt");
+        let trace = input.trace_beams(Direction::Right, 0, 0);
+
+        assert_eq!(trace.energized().len(), 46);
+        assert_eq!(trace.directions(0, 0), vec![Direction::Right]);
+        assert_eq!(trace.directions(0, 1), vec![Direction::Right, Direction::Left]);
+        assert_eq!(trace.directions(0, 2), vec![Direction::Left]);
+        assert!(trace.is_energized(1, 1));
+        assert!(!trace.is_energized(0, 6));
+
+        let rendered = input.render(&trace);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], ">|<<<\\....");
+        assert_eq!(lines[1], "|v-.\\^....");
+    }
+
+    #[test]
+    fn test_frames() {
+        let input = Input::read("examples/day16_example1.txt");
+        let trace = input.trace_beams(Direction::Right, 0, 0);
+
+        assert_eq!(trace.frame(0).energized().len(), 0);
+        assert_eq!(trace.frame(1).energized(), vec![(0, 0)]);
+        assert_eq!(trace.frame(2).energized(), vec![(0, 0), (0, 1)]);
+        assert_eq!(trace.frame(trace.steps.len()).dirs, trace.dirs);
+
+        let mut counts: Vec<usize> = (0..=trace.steps.len())
+            .map(|n| trace.frame(n).energized().len())
+            .collect();
+        let last = counts.pop().unwrap();
+        assert_eq!(last, 46);
+        assert!(counts.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn test_render_ppm() {
+        let input = Input::read("examples/day16_example1.txt");
+        let trace = input.trace_beams(Direction::Right, 0, 0);
+        let image = input.render_ppm(&trace, 3);
+
+        let header = b"P6\n30 30\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 30*30*3);
+    }
+
+    #[test]
+    fn test_engine_matches_reference() {
+        // Pseudo-random grid, sparse in mirrors and splitters.
+        let mut seed: u64 = 12345;
+        let mut grid: Vec<Vec<GridElt>> = Vec::new();
+        for _ in 0..40 {
+            let mut row: Vec<GridElt> = Vec::new();
+            for _ in 0..30 {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                row.push(match (seed >> 33) % 20 {
+                    0 => GridElt::ReflectSlash,
+                    1 => GridElt::ReflectBackslash,
+                    2 => GridElt::SplitUpDown,
+                    3 => GridElt::SplitLeftRight,
+                    _ => GridElt::Empty,
+                });
+            }
+            grid.push(row);
+        }
+        let input = Input { grid };
+
+        // Check the engine against the beam-by-beam search.
+        let mut starts: Vec<(Direction, usize, usize)> = Vec::new();
+        for row in 0..40 {
+            starts.push((Direction::Right, row, 0));
+            starts.push((Direction::Left, row, 29));
+        }
+        for col in 0..30 {
+            starts.push((Direction::Down, 0, col));
+            starts.push((Direction::Up, 39, col));
+        }
+        let counts = BeamEngine::new(&input).energize_all(&starts);
+
+        for ((dir, row, col), count) in starts.iter().zip(counts) {
+            assert_eq!(count, input.energize(*dir, *row, *col));
+        }
+    }
 }
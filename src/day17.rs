@@ -1,7 +1,4 @@
-use std::{fs::File, io::{BufReader, BufRead}, collections::HashMap};
-
-use petgraph::graphmap::DiGraphMap;
-use petgraph::algo::dijkstra;
+use std::{fs::File, io::{BufReader, BufRead}, collections::{BinaryHeap, HashMap}, cmp::Reverse, fmt::{Display, Formatter, Error}};
 
 use crate::day::{Day, Answer};
 
@@ -23,6 +20,24 @@ impl Direction {
         Direction::Right,
         Direction::Left,
     ];
+
+    fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Right => Direction::Left,
+            Direction::Left => Direction::Right,
+        }
+    }
+
+    fn delta(&self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Right => (0, 1),
+            Direction::Left => (0, -1),
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -33,14 +48,52 @@ struct NodeId {
     momentum: usize,
 }
 
+// Rules for how far a crucible may travel in a straight line.  It must
+// move at least min_run blocks before turning (or stopping at the goal) and
+// can't move more than max_run blocks without turning.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Crucible {
+    min_run: usize,
+    max_run: usize,
+}
+
+impl Crucible {
+    const NORMAL: Crucible = Crucible { min_run: 1, max_run: 3 };
+    const ULTRA: Crucible = Crucible { min_run: 4, max_run: 10 };
+
+    fn can_stop(&self, momentum: usize) -> bool {
+        momentum == 0 || momentum >= self.min_run
+    }
+}
+
+// A least-heat route: the heat lost and every state along the way, starting
+// with the start cell (momentum 0).  explored counts the states the search
+// expanded to find it.  The answers only need the heat.
+#[derive(Debug)]
+struct HeatPath {
+    heat: usize,
+    steps: Vec<NodeId>,
+    explored: usize,
+}
+
+impl HeatPath {
+    #[cfg(test)]
+    fn cells(&self) -> Vec<(usize, usize)> {
+        self.steps.iter().map(|node| (node.row, node.col)).collect()
+    }
+}
+
+impl Display for HeatPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{} heat over {} moves, {} states explored", self.heat, self.steps.len() - 1, self.explored)
+    }
+}
+
 struct Input {
     grid: Vec<Vec<usize>>,
 }
 
 impl Input {
-    const MAX_MOMENTUM: usize = 3;
-    const MAX_ULTRA_MOMENTUM: usize = 10;
-
     pub fn read(filename: &str) -> Input {
         let mut grid: Vec<Vec<usize>> = Vec::new();
 
@@ -67,31 +120,23 @@ impl Input {
         self.grid[0].len()
     }
 
-    // Determines if move_dir is a valid move under the circumstances.
-    // If so, returns Some(new_row, new_col, new_momentum)
+    // Determines if move_dir is a valid move for the crucible from this state.
+    // If so, returns Some(new state)
     // Otherwise, returns None
-    fn valid_move(&self, row: usize, col: usize, 
-                 dir: Direction, momentum: usize, 
-                 move_dir: Direction) -> Option<(usize, usize, usize)> 
-                 {
-        let opposite: HashMap<Direction, Direction> = HashMap::from([
-            (Direction::Up, Direction::Down),
-            (Direction::Down, Direction::Up),
-            (Direction::Right, Direction::Left),
-            (Direction::Left, Direction::Right),
-        ]);
-
-        let dy_dx: HashMap<Direction, (isize, isize)> = HashMap::from([
-            (Direction::Up, (-1, 0)),
-            (Direction::Down, (1, 0)),
-            (Direction::Right, (0, 1)),
-            (Direction::Left, (0, -1)),
-        ]);
-
-        if (move_dir == opposite[&dir]) && (momentum > 0) { 
-            // Can't reverse
-            return None;
-        };
+    fn valid_move(&self, crucible: &Crucible, from: &NodeId, move_dir: Direction) -> Option<NodeId> {
+        let NodeId { row, col, dir, momentum } = *from;
+
+        if momentum > 0 {
+            if move_dir == dir.opposite() {
+                // Can't reverse
+                return None;
+            }
+
+            if move_dir != dir && momentum < crucible.min_run {
+                // Can't change direction until the minimum run is done
+                return None;
+            }
+        }
 
         let new_momentum = if (move_dir == dir) && (momentum > 0) {
             // increase momentum since we are moving in the same dir
@@ -103,175 +148,118 @@ impl Input {
         };
 
         // Limit momentum build up
-        if new_momentum > Self::MAX_MOMENTUM { 
+        if new_momentum > crucible.max_run {
             // continuing to move in same dir, momentum would exceed limit
             return None;
         };
 
-        let (delta_row, delta_col) = dy_dx[&move_dir];
-        let new_row = row as isize + delta_row;
-        let new_col = col as isize + delta_col;
-        if (new_row < 0) || (new_row as usize >= self.rows()) ||
-           (new_col < 0) || (new_col as usize >= self.cols()) {
+        let (delta_row, delta_col) = move_dir.delta();
+        let new_row = row.checked_add_signed(delta_row)?;
+        let new_col = col.checked_add_signed(delta_col)?;
+        if new_row >= self.rows() || new_col >= self.cols() {
             // We went off the edge of the map
             return None;
         }
-        let new_row = new_row as usize;
-        let new_col = new_col as usize;
 
-        Some((new_row, new_col, new_momentum))
+        Some(NodeId { row: new_row, col: new_col, dir: move_dir, momentum: new_momentum })
     }
 
-    // Determines if move_dir is a valid move under the circumstances.
-    // If so, returns Some(new_row, new_col, new_momentum)
-    // Otherwise, returns None
-    fn valid_ultra_move(&self, row: usize, col: usize, 
-        dir: Direction, momentum: usize, 
-        move_dir: Direction) -> Option<(usize, usize, usize)> 
-        {
-        let opposite: HashMap<Direction, Direction> = HashMap::from([
-        (Direction::Up, Direction::Down),
-        (Direction::Down, Direction::Up),
-        (Direction::Right, Direction::Left),
-        (Direction::Left, Direction::Right),
-        ]);
-
-        let dy_dx: HashMap<Direction, (isize, isize)> = HashMap::from([
-        (Direction::Up, (-1, 0)),
-        (Direction::Down, (1, 0)),
-        (Direction::Right, (0, 1)),
-        (Direction::Left, (0, -1)),
-        ]);
-
-        // Can't reverse (Is this a constraint, still?)
-        if (move_dir == opposite[&dir]) && (momentum > 0) { 
-            // Can't reverse
-            return None;
-        };
-
-        // Can't change direction until momentum is 4 or greater
-        if (move_dir != dir) && (momentum > 0) && (momentum < 4) {
-            return None;
-        }
+    // Best route for the crucible between two blocks, if there is one.
+    fn best_path(&self, crucible: &Crucible, start: (usize, usize), goal: (usize, usize)) -> Option<HeatPath> {
+        self.search(crucible, start, goal, true)
+    }
 
-        // Can't let momentum go above 10
-        let new_momentum = if (move_dir == dir) && (momentum > 0) {
-            // increase momentum since we are moving in the same dir
-            momentum + 1
+    // Shortest-path search over (block, direction, momentum) states.  The
+    // states are generated as they're reached rather than built up front, and
+    // the search stops at the first state that reaches the goal.
+    //
+    // With use_heuristic, this is A*: the estimate of remaining heat is the
+    // Manhattan distance to the goal times the least heat of any block, which
+    // never overestimates.  Without it, this is plain Dijkstra.
+    fn search(&self, crucible: &Crucible, start: (usize, usize), goal: (usize, usize),
+              use_heuristic: bool) -> Option<HeatPath> {
+        let min_heat = if use_heuristic {
+            *self.grid.iter().flatten().min().unwrap()
         }
         else {
-            // reset momentum to 1
-            1
+            0
         };
-        if new_momentum > Self::MAX_ULTRA_MOMENTUM { 
-            // exceeded momentum limit
-            return None;
+        let estimate = |node: &NodeId| {
+            (node.row.abs_diff(goal.0) + node.col.abs_diff(goal.1)) * min_heat
         };
 
-        let (delta_row, delta_col) = dy_dx[&move_dir];
-        let new_row = row as isize + delta_row;
-        let new_col = col as isize + delta_col;
-        if (new_row < 0) || (new_row as usize >= self.rows()) ||
-           (new_col < 0) || (new_col as usize >= self.cols()) {
-            // We went off the edge of the map
-            return None;
-        }
-        let new_row = new_row as usize;
-        let new_col = new_col as usize;
-
-        Some((new_row, new_col, new_momentum))
-    }
-
-    fn to_graph(&self, ultra: bool) -> DiGraphMap<NodeId, usize> {
-
+        // state -> (heat lost getting there, previous state)
+        let mut best: HashMap<NodeId, (usize, Option<NodeId>)> = HashMap::new();
+        // (estimated total, heat so far, state)
+        let mut queue: BinaryHeap<Reverse<(usize, usize, NodeId)>> = BinaryHeap::new();
 
-        let mut g: DiGraphMap<NodeId, usize> = DiGraphMap::new();
+        let start = NodeId { row: start.0, col: start.1, dir: Direction::Right, momentum: 0 };
+        best.insert(start, (0, None));
+        queue.push(Reverse((estimate(&start), 0, start)));
 
-        /*
-        // add nodes (Not neccessary as adding edges does it automatically!)
-        // 13 nodes per board position: one for each combination of 
-        // direction and momentum.
-        for row in 0..self.rows() {
-            for col in 0..self.cols() {
-                for dir in Direction::DIRECTIONS {
-                    for momentum in 0..=3 {
-                        // Add a node to the graph for this position/momentum combo
-                        let node = NodeId { row, col, dir, momentum };
-                        g.add_node(node);
+        let mut explored = 0;
+        while let Some(Reverse((_, heat, node))) = queue.pop() {
+            if heat > best[&node].0 {
+                // A cheaper way here was already handled.
+                continue;
+            }
+            explored += 1;
+
+            if (node.row, node.col) == goal && crucible.can_stop(node.momentum) {
+                // Follow the previous states back to the start.
+                let steps = {
+                    let mut steps: Vec<NodeId> = vec![node];
+                    while let Some(prev) = best[steps.last().unwrap()].1 {
+                        steps.push(prev);
                     }
-                }
+                    steps.reverse();
+                    steps
+                };
+
+                return Some(HeatPath { heat, steps, explored });
             }
-        }
-        */
-
-        // add edges
-        // From every position on the board, add edges from nodes corresponding 
-        // to all the momentum states at that position.  Don't create edges that
-        // reverse momentum and don't create edges that increase momentum past
-        // the maximum.  When continuing in same direction, increase momentum
-        // but when changing direction, reset momentum to 1.
-        for row in 0..self.rows() {
-            for col in 0..self.cols() {
-                for dir in Direction::DIRECTIONS {
-                    for momentum in 0..=10 {
-                        // Starting from the state with this position, momentum
-                        // Test whether we can move in each direction and add
-                        // an appropriate destination state if so.
-                        for move_dir in Direction::DIRECTIONS {
-                            let valid_move = if ultra {
-                                self.valid_ultra_move(row, col, dir, momentum, move_dir)
-                            }
-                            else {
-                                self.valid_move(row, col, dir, momentum, move_dir)
-                            };
-
-                            if let Some((new_row, new_col, new_momentum)) = valid_move {
-                                // Everything is good for a transition from (row, col, dir, momentum) to 
-                                // (new_row, new_col, move_dir, new_momentum) with cost self.grid[new_row][new_col];
-                                // Add an edge to the graph for this move.
-                                let from = NodeId { row, col, dir, momentum };
-                                let to = NodeId { row:new_row, col: new_col, dir: move_dir, momentum: new_momentum };
-                                g.add_edge(from, to, self.grid[new_col][new_row]);
-                            }
-                        }
+
+            for move_dir in Direction::DIRECTIONS {
+                if let Some(next) = self.valid_move(crucible, &node, move_dir) {
+                    let next_heat = heat + self.grid[next.row][next.col];
+                    let improved = match best.get(&next) {
+                        Some((known_heat, _)) => next_heat < *known_heat,
+                        None => true,
+                    };
+                    if improved {
+                        best.insert(next, (next_heat, Some(node)));
+                        queue.push(Reverse((next_heat + estimate(&next), next_heat, next)));
                     }
                 }
             }
         }
 
-        g
+        None
     }
 
-    pub fn least_heat(&self, ultra: bool) -> usize {
-        // get the graph representation of possible movements.
-        let g = self.to_graph(ultra);
-        
-        // println!("We have a directed graph and that's exciting.");
-
-        // Do Dijkstra to get cost (heat loss) to every node.
-        let start = NodeId{ row: 0, col: 0, dir:Direction::Right, momentum: 0 };
-        let distances = dijkstra(
-            &g,                   // graph
-            start,                     // start
-            None,                 // goal
-            |edge| *edge.2  // edge cost
-        );
-        // println!("We have dijkstra results and that's more exciting.");
-
-        // The final position corresponds to several graph nodes
-        // (because there are many momentum states)
-        // Pick the best one.
-        let min_dist = distances.iter()
-            .filter(|(nodeid, _d)| (nodeid.row == self.rows()-1) && (nodeid.col == self.cols()-1))
-            .filter(|(nodeid, _d)| !ultra || (nodeid.momentum >= 4))
-            .map(|(_nodeid, d)| d)
-            .min()
-            .unwrap();
-
-        // Bob's your uncle.
-        *min_dist
+    // Best route from the top-left to the bottom-right block.
+    pub fn least_heat(&self, crucible: &Crucible) -> HeatPath {
+        self.best_path(crucible, (0, 0), (self.rows()-1, self.cols()-1)).unwrap()
     }
 
+    // The grid with the route drawn on it, as in the puzzle.
+    #[cfg(test)]
+    fn render_path(&self, path: &HeatPath) -> String {
+        let mut cells: Vec<Vec<char>> = self.grid.iter()
+            .map(|line| line.iter().map(|h| char::from_digit(*h as u32, 10).unwrap()).collect())
+            .collect();
+
+        for node in path.steps.iter().skip(1) {
+            cells[node.row][node.col] = match node.dir {
+                Direction::Up => '^',
+                Direction::Down => 'v',
+                Direction::Right => '>',
+                Direction::Left => '<',
+            };
+        }
+
+        cells.iter().map(|line| line.iter().collect::<String>() + "\n").collect()
+    }
 }
 
 pub struct Day17<'a> {
@@ -287,18 +275,18 @@ impl<'a> Day17<'a> {
 impl<'a> Day for Day17<'a> {
     fn part1(&self) -> Answer {
         let input = Input::read(self.input_filename);
-        Answer::Numeric(input.least_heat(false))
+        Answer::Numeric(input.least_heat(&Crucible::NORMAL).heat)
     }
 
     fn part2(&self) -> Answer {
         let input = Input::read(self.input_filename);
-        Answer::Numeric(input.least_heat(true))
+        Answer::Numeric(input.least_heat(&Crucible::ULTRA).heat)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day17::Input;
+    use crate::day17::{Input, Crucible};
 
     #[test]
     fn test_input() {
@@ -310,13 +298,87 @@ mod tests {
     #[test]
     fn test_least_heat() {
         let input = Input::read("examples/day17_example1.txt");
-        assert_eq!(input.least_heat(false), 102);
+        assert_eq!(input.least_heat(&Crucible::NORMAL).heat, 102);
     }
 
 
     #[test]
     fn test_ultra_least_heat() {
         let input = Input::read("examples/day17_example1.txt");
-        assert_eq!(input.least_heat(true), 94);
+        assert_eq!(input.least_heat(&Crucible::ULTRA).heat, 94);
+    }
+
+    #[test]
+    fn test_path() {
+        let input = Input::read("examples/day17_example1.txt");
+        let path = input.least_heat(&Crucible::NORMAL);
+        let cells = path.cells();
+
+        assert_eq!(cells[0], (0, 0));
+        assert_eq!(*cells.last().unwrap(), (12, 12));
+
+        // Heat is the sum over the blocks entered.
+        let heat: usize = cells.iter().skip(1).map(|(row, col)| input.grid[*row][*col]).sum();
+        assert_eq!(heat, path.heat);
+
+        let rendered = input.render_path(&path);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(lines[0].chars().next(), Some('2'));
+        assert!(matches!(lines[12].chars().last(), Some('v') | Some('>')));
+        assert_eq!(rendered.chars().filter(|c| "^v<>".contains(*c)).count(), cells.len() - 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_custom_rules() {
+        let input = Input::read("examples/day17_example1.txt");
+
+        // With no straight-line limit the route only has to avoid reversing.
+        let free = Crucible { min_run: 1, max_run: 13 };
+        assert!(input.least_heat(&free).heat <= 102);
+
+        // Runs that can't reach the goal exactly give no route.
+        let path = input.best_path(&Crucible { min_run: 5, max_run: 5 }, (0, 0), (12, 12));
+        assert!(path.is_none());
+
+        let path = input.best_path(&Crucible::NORMAL, (2, 3), (2, 4)).unwrap();
+        assert_eq!(path.heat, input.grid[2][4]);
+        assert_eq!(path.cells(), vec![(2, 3), (2, 4)]);
+
+        let path = input.best_path(&Crucible::NORMAL, (5, 5), (5, 5)).unwrap();
+        assert_eq!(path.heat, 0);
+        assert_eq!(path.to_string(), "0 heat over 0 moves, 1 states explored");
+    }
+
+    #[test]
+    fn test_search_strategies() {
+        let input = Input::read("examples/day17_example1.txt");
+        let goal = (input.rows()-1, input.cols()-1);
+
+        for crucible in [Crucible::NORMAL, Crucible::ULTRA] {
+            let dijkstra = input.search(&crucible, (0, 0), goal, false).unwrap();
+            let astar = input.search(&crucible, (0, 0), goal, true).unwrap();
+
+            assert_eq!(dijkstra.heat, astar.heat);
+            assert!(astar.explored <= dijkstra.explored);
+
+            // Stopping at the goal explores fewer than all the states.
+            assert!(dijkstra.explored < input.rows() * input.cols() * 4 * (crucible.max_run + 1));
+        }
+    }
+
+    #[test]
+    fn test_rectangular_grid() {
+        let input = Input { grid: vec![
+            vec![1, 1, 1, 1, 1, 1, 1, 1],
+            vec![9, 9, 9, 9, 9, 9, 9, 1],
+        ]};
+
+        let path = input.least_heat(&Crucible { min_run: 1, max_run: 10 });
+        assert_eq!(path.heat, 8);
+        assert_eq!(path.cells().last(), Some(&(1, 7)));
+
+        let path = input.least_heat(&Crucible::NORMAL);
+        assert!(path.heat > 8);
+    }
+}
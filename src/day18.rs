@@ -1,8 +1,10 @@
-use std::{fs::File, io::{BufReader, BufRead}, collections::HashMap};
+use std::{fs::File, io::{BufReader, BufRead}};
 
-use crate::day::{Day, Answer};
+use crate::{day::{Day, Answer}, polygon::Polygon};
 use lazy_static::lazy_static;
 use regex::Regex;
+#[cfg(test)]
+use rgb::RGB8;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 enum Direction {
@@ -12,20 +14,35 @@ enum Direction {
     Left,
 }
 
+impl Direction {
+    // (delta_row, delta_col)
+    fn delta(&self) -> (isize, isize) {
+        match self {
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::Left => (0, -1),
+            Direction::Right => (0, 1),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 struct Instruction {
     dir: Direction,
     dist: usize,
     dir2: Direction,
     dist2: usize,
+    // Only the renderers use the colour.
+    #[cfg(test)]
+    color: RGB8,
 }
 
 lazy_static! {
     // caps[1]: "R", "L", "U", "D"
     // caps[2]: number, distance
-    // caps[3]: two hex digits for red
-    // caps[4]: two hex digits for green
-    // caps[5]: two hex digits for blue
+    // caps[3]: five hex digits, part 2 distance
+    // caps[4]: one hex digit, part 2 direction
+    // Together caps[3] and caps[4] are the colour, #rrggbb.
     static ref INSTR_RE: Regex = Regex::new("([RLUD]) ([0-9]+) \\(#([0-9a-f]{5})([0-9a-f]{1})\\)").unwrap();
 }
 
@@ -50,13 +67,18 @@ impl Instruction {
                 _ => panic!("Bad direction")
             };
 
-            Some( Instruction { dir, dist, dir2, dist2 } )
+            Some( Instruction { dir, dist, dir2, dist2,
+                                #[cfg(test)] color: parse_color(&format!("{}{}", &caps[3], &caps[4])) } )
         }
         else {
             None
         }
     }
 
+    fn dir_dist(&self) -> (Direction, usize) {
+        (self.dir, self.dist)
+    }
+
     fn dir2_dist2(&self) -> (Direction, usize) {
         (self.dir2, self.dist2)
     }
@@ -66,67 +88,6 @@ struct Input {
     instructions: Vec<Instruction>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum InOutState {
-    Outside,
-    NorthBorder,
-    SouthBorder,
-    Inside
-}
-
-struct InOutFsm {
-    state: InOutState,
-}
-
-impl InOutFsm {
-    fn new() -> InOutFsm {
-        InOutFsm { state: InOutState::Outside}
-    }
-
-    // Returns true if in the interior
-    fn step(&mut self, cell: bool, above: bool, below: bool) -> bool {
-        self.state = match (&self.state, cell, above, below) {
-            // If cell is false, state can't change
-            (&InOutState::Outside, false, _, _) => { InOutState::Outside }
-            (&InOutState::Inside,  false, _, _) => { InOutState::Inside  }
-            (&InOutState::NorthBorder, false, _, _) => { panic!("Weird transition") }
-            (&InOutState::SouthBorder,  false, _, _) => { panic!("Weird transition")  }
-
-            // Other combos from Outside
-            (&InOutState::Outside, true, false, false) => { panic!("Weird transition") }
-            (&InOutState::Outside, true, false, true) => { InOutState::NorthBorder }
-            (&InOutState::Outside, true, true, false) => { InOutState::SouthBorder }
-            (&InOutState::Outside, true, true, true) => { InOutState::Inside }
-
-            // Other combos from Inside
-            (&InOutState::Inside, true, false, false) => { panic!("Weird transition") }
-            (&InOutState::Inside, true, false, true) => { InOutState::SouthBorder }
-            (&InOutState::Inside, true, true, false) => { InOutState::NorthBorder }
-            (&InOutState::Inside, true, true, true) => { InOutState::Outside }
-
-            // Other combos from North Border
-            (&InOutState::NorthBorder, true, false, false) => { InOutState::NorthBorder }
-            (&InOutState::NorthBorder, true, false, true) => { InOutState::Outside }
-            (&InOutState::NorthBorder, true, true, false) => { InOutState::Inside }
-            (&InOutState::NorthBorder, true, true, true) => { panic!("Weird transition") }
-
-            // Other combos from South Border
-            (&InOutState::SouthBorder, true, false, false) => { InOutState::SouthBorder }
-            (&InOutState::SouthBorder, true, false, true) => { InOutState::Inside }
-            (&InOutState::SouthBorder, true, true, false) => { InOutState::Outside }
-            (&InOutState::SouthBorder, true, true, true) => { panic!("weird transition") }
-        };
-
-        // Return true if in interior (Any place on the border counts as inside.)
-        cell || (self.state != InOutState::Outside)
-
-    }
-
-    fn reset(&mut self) {
-        self.state = InOutState::Outside;
-    }
-}
-
 impl Input {
     pub fn read(filename: &str) -> Input {
         let f = File::open(filename).unwrap();
@@ -142,120 +103,45 @@ impl Input {
         Input { instructions }
     }
 
-    pub fn volume(&self) -> usize {
-
-        // Direction -> (delta_row, delta_cp;)
-        let delta: HashMap<Direction, (isize, isize)> = HashMap::from([
-            (Direction::Up, (-1, 0)),
-            (Direction::Down, (1, 0)),
-            (Direction::Left, (0, -1)),
-            (Direction::Right, (0, 1)),
-        ]);
-        let mut border: HashMap<(isize, isize), isize> = HashMap::new();        
-        let mut interior: HashMap<(isize, isize), isize> = HashMap::new();
-
-        let mut min_row = 0;
-        let mut min_col = 0;
-        let mut max_row = 0;
-        let mut max_col = 0;
-
-        // Interpret instructions from input, drawing outline
-        let (mut row, mut col) = (0, 0);
-        border.insert((row, col), 1);
-
-        for i in &self.instructions {
-            let (delta_row, delta_col) = delta[&i.dir];
-
-            for _n in 0..i.dist {
-                row += delta_row;
-                col += delta_col;
-                border.insert((row, col), 1);
-            }
+    // Outline traced by the digger, using the chosen reading of each instruction.
+    fn lagoon<F>(&self, dir_dist: F) -> Polygon
+    where F: Fn(&Instruction) -> (Direction, usize) {
+        Polygon::from_moves((0, 0), self.instructions.iter().map(|i| {
+            let (dir, dist) = dir_dist(i);
+            (dir.delta(), dist)
+        }))
+    }
 
-            if row < min_row { min_row = row; }
-            if row > max_row { max_row = row; }
-            if col < min_col { min_col = col; }
-            if col > max_col { max_col = col; }
-        }
-        
-        let mut fsm = InOutFsm::new();
-        for row in min_row..=max_row {
-            fsm.reset();
-            for col in min_col..=max_col {
-                let cell = border.contains_key( &(row, col) );
-                let above = border.contains_key( &(row-1, col) );
-                let below = border.contains_key( &(row+1, col) );
-                if fsm.step(cell, above, below) {
-                    // Interior cell, make sure it's in map
-                    interior.insert((row, col), 1);
-                }
-            }
-        }
+    #[cfg(test)]
+    fn dig<F>(&self, dir_dist: F) -> Dig
+    where F: Fn(&Instruction) -> (Direction, usize) {
+        Dig::new(self, dir_dist)
+    }
 
-        interior.len()
+    pub fn volume(&self) -> usize {
+        self.lagoon(Instruction::dir_dist).total()
     }
 
     pub fn volume2(&self) -> usize {
-        // Direction -> (delta_row, delta_cp;)
-        let delta: HashMap<Direction, (isize, isize)> = HashMap::from([
-            (Direction::Up, (-1, 0)),
-            (Direction::Down, (1, 0)),
-            (Direction::Left, (0, -1)),
-            (Direction::Right, (0, 1)),
-        ]);
-        let mut coords: Vec<(isize, isize)> = Vec::new();
-        let mut moves = 0;
-
-        // Construct a list of coordinates making up the perimeter.
-        let (mut row, mut col) = (0, 0);
-        coords.push((row, col));
-
-        for i in &self.instructions {
-            let (dir2, dist2) = i.dir2_dist2();
-            moves += dist2;
-            let (delta_row, delta_col) = delta[&dir2];
-            row = row + delta_row * dist2 as isize;
-            col = col + delta_col * dist2 as isize;
-
-            // println!("Pushing coord ({row}, {col})");
-            coords.push((row, col));
-        }
-
-        // Sum up the contribution from all the point pairs
-        let mut sum = 0;
-        let (mut x1, mut y1) = coords[0];
-        for n in 0..coords.len()-1 {
-            let (x0, y0) = (x1, y1);
-            x1 = coords[n+1].0;
-            y1 = coords[n+1].1;
-            sum += x0*y1 - y0*x1;
-            // println!("  {x0}*{y1} - {y0}*{x1} = {}", x0*y1 - y0*x1);
-            // println!("  sum: {sum}");
-        }
-
-        // Don't forget the wrap around
-        let (x0, y0) = (x1, y1);
-        x1 = coords[0].0;
-        y1 = coords[0].1;
-        sum += x0*y1 - y0*x1;
-        // println!("  {x0}*{y1} - {y0}*{x1} = {}", x0*y1 - y0*x1);
-        // println!("  sum: {sum}");
-
-        let sum = sum.abs() as usize;
-
-        let area = sum/2 + moves/2 + 1;
-
-        area
+        self.lagoon(Instruction::dir2_dist2).total()
     }
 }
 
 
+// One straight run of trench, dug by a single instruction.
+#[cfg(test)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Trench {
+    from: (isize, isize),
+    to: (isize, isize),
+    color: RGB8,
+}
 
-/*
+// The trench dug around the lagoon, in (row, col) with the digger starting
+// at (0, 0).
+#[cfg(test)]
 struct Dig {
-    // maps (row, col) to depth.
-    border: HashMap<(isize, isize), isize>,
-    interior: HashMap<(isize, isize), isize>,
+    trenches: Vec<Trench>,
 
     min_row: isize,
     min_col: isize,
@@ -263,19 +149,139 @@ struct Dig {
     max_col: isize,
 }
 
+#[cfg(test)]
 impl Dig {
-    pub fn new(input: &Input) -> Dig {
+    pub fn new<F>(input: &Input, dir_dist: F) -> Dig
+    where F: Fn(&Instruction) -> (Direction, usize) {
+        let mut trenches: Vec<Trench> = Vec::new();
+        let (mut min_row, mut min_col, mut max_row, mut max_col) = (0, 0, 0, 0);
+
+        let mut from = (0, 0);
+        for i in &input.instructions {
+            let (dir, dist) = dir_dist(i);
+            let (delta_row, delta_col) = dir.delta();
+            let to = (from.0 + delta_row * dist as isize, from.1 + delta_col * dist as isize);
+            trenches.push(Trench { from, to, color: i.color });
+
+            min_row = min_row.min(to.0);
+            max_row = max_row.max(to.0);
+            min_col = min_col.min(to.1);
+            max_col = max_col.max(to.1);
+            from = to;
+        }
+
+        Dig { trenches, min_row, min_col, max_row, max_col }
+    }
 
+    pub fn rows(&self) -> usize {
+        (self.max_row - self.min_row + 1) as usize
+    }
 
-        Dig { border, interior, min_row, min_col, max_row, max_col }
+    pub fn cols(&self) -> usize {
+        (self.max_col - self.min_col + 1) as usize
     }
 
+    // Colour of every cell, None where nothing was dug.  Trench cells take
+    // the colour of the last instruction to dig them, and the lagoon inside
+    // gets the fill colour.
+    fn cells(&self, fill: RGB8) -> Vec<Vec<Option<RGB8>>> {
+        let mut cells: Vec<Vec<Option<RGB8>>> = vec![vec![None; self.cols()]; self.rows()];
+
+        // Scan along the middle of each row of cells.  The inside is between
+        // alternate crossings of the vertical trenches.
+        for (index, line) in cells.iter_mut().enumerate() {
+            let row = self.min_row + index as isize;
+            let mut crossings: Vec<isize> = self.trenches.iter()
+                .filter(|t| t.from.1 == t.to.1 && t.from.0.min(t.to.0) <= row && row < t.from.0.max(t.to.0))
+                .map(|t| t.from.1)
+                .collect();
+            crossings.sort();
+
+            for pair in crossings.chunks(2) {
+                if let [left, right] = pair {
+                    for col in *left..=*right {
+                        line[(col - self.min_col) as usize] = Some(fill);
+                    }
+                }
+            }
+        }
 
-}
+        for t in &self.trenches {
+            let (delta_row, delta_col) = ((t.to.0 - t.from.0).signum(), (t.to.1 - t.from.1).signum());
+            let mut at = t.from;
+            loop {
+                cells[(at.0 - self.min_row) as usize][(at.1 - self.min_col) as usize] = Some(t.color);
+                if at == t.to {
+                    break;
+                }
+                at = (at.0 + delta_row, at.1 + delta_col);
+            }
+        }
+
+        cells
+    }
 
-*/
+    // A binary PPM image with `scale` pixels per cell.  Only practical when
+    // the dig is small, as in part 1.
+    pub fn render_ppm(&self, scale: usize) -> Vec<u8> {
+        const GROUND: RGB8 = RGB8 { r: 24, g: 20, b: 16 };
+        const LAGOON: RGB8 = RGB8 { r: 96, g: 64, b: 40 };
+
+        let width = self.cols() * scale;
+        let height = self.rows() * scale;
+        let mut image: Vec<u8> = format!("P6\n{width} {height}\n255\n").into_bytes();
+
+        for line in self.cells(LAGOON) {
+            for _ in 0..scale {
+                for cell in &line {
+                    let pixel = cell.unwrap_or(GROUND);
+                    for _ in 0..scale {
+                        image.extend([pixel.r, pixel.g, pixel.b]);
+                    }
+                }
+            }
+        }
 
+        image
+    }
+
+    // An SVG drawing: the lagoon as a filled polygon through the cell
+    // centres, then each trench as a line in its colour.  Its size doesn't
+    // depend on the distances dug, so this also works for part 2.
+    pub fn to_svg(&self) -> String {
+        let width = self.max_col - self.min_col + 1;
+        let height = self.max_row - self.min_row + 1;
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {width} {height}\">\n",
+            self.min_col as f64 - 0.5, self.min_row as f64 - 0.5);
+
+        let points: Vec<String> = self.trenches.iter()
+            .map(|t| format!("{},{}", t.from.1, t.from.0))
+            .collect();
+        svg += &format!("  <polygon points=\"{}\" fill=\"#604028\"/>\n", points.join(" "));
+
+        for t in &self.trenches {
+            svg += &format!(
+                "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"2\" vector-effect=\"non-scaling-stroke\"/>\n",
+                t.from.1, t.from.0, t.to.1, t.to.0, hex_color(t.color));
+        }
+
+        svg += "</svg>\n";
+        svg
+    }
+}
+
+// rrggbb to a colour
+#[cfg(test)]
+fn parse_color(hex: &str) -> RGB8 {
+    let rgb = u32::from_str_radix(hex, 16).unwrap();
+    RGB8 { r: (rgb >> 16) as u8, g: (rgb >> 8) as u8, b: rgb as u8 }
+}
 
+#[cfg(test)]
+fn hex_color(color: RGB8) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
 
 pub struct Day18<'a> {
     input_filename: &'a str,
@@ -303,7 +309,8 @@ impl<'a> Day for Day18<'a> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{day18::{Input, Direction, Day18}, day::{Answer, Day}};
+    use crate::{day18::{Input, Instruction, Direction, Day18}, day::{Answer, Day}};
+    use rgb::RGB8;
 
     #[test]
     fn test_input() {
@@ -314,6 +321,7 @@ mod tests {
         assert_eq!(input.instructions[0].dist, 6);
         assert_eq!(input.instructions[0].dir2, Direction::Right);
         assert_eq!(input.instructions[0].dist2, 0x70c71);
+        assert_eq!(input.instructions[0].color, RGB8 { r: 0x70, g: 0xc7, b: 0x10 });
     }
 
     #[test]
@@ -330,6 +338,62 @@ mod tests {
         assert_eq!(input.volume2(), 952408144115);
     }
 
+    #[test]
+    fn test_lagoon() {
+        let input = Input::read("examples/day18_example1.txt");
+        let lagoon = input.lagoon(Instruction::dir_dist);
+
+        assert_eq!(lagoon.boundary(), 38);
+        assert_eq!(lagoon.interior(), 24);
+        assert_eq!(lagoon.signed_area2().abs(), 2 * 42);
+    }
+
+    #[test]
+    fn test_dig() {
+        let input = Input::read("examples/day18_example1.txt");
+        let dig = input.dig(Instruction::dir_dist);
+
+        assert_eq!(dig.trenches.len(), 14);
+        assert_eq!((dig.rows(), dig.cols()), (10, 7));
+
+        let cells = dig.cells(RGB8 { r: 0, g: 0, b: 0 });
+        assert_eq!(cells.iter().flatten().filter(|c| c.is_some()).count(), 62);
+        assert_eq!(cells[0][1], Some(RGB8 { r: 0x70, g: 0xc7, b: 0x10 }));
+        // The corner where the outline closes was last dug by the final instruction.
+        assert_eq!(cells[0][0], Some(RGB8 { r: 0x7a, g: 0x21, b: 0xe3 }));
+        assert_eq!(cells[1][1], Some(RGB8 { r: 0, g: 0, b: 0 }));
+        assert_eq!(cells[3][0], None);
+    }
+
+    #[test]
+    fn test_render_ppm() {
+        let input = Input::read("examples/day18_example1.txt");
+        let image = input.dig(Instruction::dir_dist).render_ppm(2);
+
+        let header = b"P6\n14 20\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 14*20*3);
+        // Second pixel of the second cell
+        let at = header.len() + 3*3;
+        assert_eq!(&image[at..at+3], &[0x70, 0xc7, 0x10]);
+    }
+
+    #[test]
+    fn test_svg() {
+        let input = Input::read("examples/day18_example1.txt");
+
+        let svg = input.dig(Instruction::dir_dist).to_svg();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-0.5 -0.5 7 10\">"));
+        assert_eq!(svg.matches("<line ").count(), 14);
+        assert!(svg.contains("x1=\"0\" y1=\"0\" x2=\"6\" y2=\"0\" stroke=\"#70c710\""));
+        assert!(svg.trim_end().ends_with("</svg>"));
+
+        // Same number of elements at the part 2 scale.
+        let svg = input.dig(Instruction::dir2_dist2).to_svg();
+        assert_eq!(svg.matches("<line ").count(), 14);
+        assert!(svg.contains("x2=\"461937\""));
+    }
+
     #[test]
     fn test_part1() {
         let d = Day18::new("examples/day18_example1.txt");
@@ -1,6 +1,6 @@
-use std::{cmp::{max, min}, collections::HashMap, fs::File, io::{BufReader, BufRead}};
+use std::{cmp::{max, min}, collections::HashMap, fs::File, io::{BufReader, BufRead}, fmt::{Display, Formatter, Error}};
 
-use crate::day::{Day, Answer};
+use crate::{day::{Day, Answer}, bitset::BitSet};
 
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -11,6 +11,7 @@ lazy_static! {
     static ref PART_RE: Regex = Regex::new("\\{x=([0-9]+),m=([0-9]+),a=([0-9]+),s=([0-9]+)\\}").unwrap();
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Condition {
     True,
     XLt(usize),
@@ -147,7 +148,18 @@ impl Part {
     }
 }
 
-#[derive(Clone, Copy)]
+// The four rating categories
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    X,
+    M,
+    A,
+    S,
+}
+
+// A box of parts: each rating between its min and max, inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Domain {
     x_min: usize,
     x_max: usize,
@@ -171,15 +183,63 @@ const EMPTY_DOMAIN: Domain = Domain {
 };
 
 impl Domain {
+    // Every rating from 1 to 4000, as in the puzzle.
     fn new() -> Domain {
-        Domain { 
-            x_min: 1, x_max: 4000,
-            m_min: 1, m_max: 4000,
-            a_min: 1, a_max: 4000,
-            s_min: 1, s_max: 4000,
+        Domain::with_bounds(1, 4000)
+    }
+
+    // Every rating from min to max
+    fn with_bounds(min: usize, max: usize) -> Domain {
+        Domain {
+            x_min: min, x_max: max,
+            m_min: min, m_max: max,
+            a_min: min, a_max: max,
+            s_min: min, s_max: max,
         }
     }
 
+    #[cfg(test)]
+    fn range(&self, category: Category) -> (usize, usize) {
+        match category {
+            Category::X => (self.x_min, self.x_max),
+            Category::M => (self.m_min, self.m_max),
+            Category::A => (self.a_min, self.a_max),
+            Category::S => (self.s_min, self.s_max),
+        }
+    }
+
+    // The part of this domain with the category's rating in min..=max
+    #[cfg(test)]
+    fn restrict(&self, category: Category, min: usize, max: usize) -> Domain {
+        let mut domain = *self;
+        let (lo, hi) = match category {
+            Category::X => (&mut domain.x_min, &mut domain.x_max),
+            Category::M => (&mut domain.m_min, &mut domain.m_max),
+            Category::A => (&mut domain.a_min, &mut domain.a_max),
+            Category::S => (&mut domain.s_min, &mut domain.s_max),
+        };
+        *lo = (*lo).max(min);
+        *hi = (*hi).min(max);
+
+        domain
+    }
+
+    #[cfg(test)]
+    fn intersect(&self, other: &Domain) -> Domain {
+        self.restrict(Category::X, other.x_min, other.x_max)
+            .restrict(Category::M, other.m_min, other.m_max)
+            .restrict(Category::A, other.a_min, other.a_max)
+            .restrict(Category::S, other.s_min, other.s_max)
+    }
+
+    #[cfg(test)]
+    fn contains(&self, part: &Part) -> bool {
+        (self.x_min..=self.x_max).contains(&part.x) &&
+        (self.m_min..=self.m_max).contains(&part.m) &&
+        (self.a_min..=self.a_max).contains(&part.a) &&
+        (self.s_min..=self.s_max).contains(&part.s)
+    }
+
     // split into subdomains for condition false, condition true
     fn split(&self, cond: &Condition) -> (Domain, Domain) {
         let mut f_domain = *self; // copy
@@ -268,10 +328,293 @@ impl Domain {
 
         x_size * m_size * a_size * s_size
     }
+
+    fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+}
+
+// Where a rule sends a part, once workflow names are resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Accept,
+    Reject,
+    Flow(usize),
+}
+
+// A box of accepted parts, and the rules that accept them: each step is a
+// workflow and the number of the rule that sent the parts on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Region {
+    domain: Domain,
+    path: Vec<(String, usize)>,
+}
+
+// "x=1..=1415 m=1..=4000 a=1..=2005 s=1..=1350 via in[0] px[0] qkq[0]"
+impl Display for Region {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let d = &self.domain;
+        write!(f, "x={}..={} m={}..={} a={}..={} s={}..={} via",
+            d.x_min, d.x_max, d.m_min, d.m_max, d.a_min, d.a_max, d.s_min, d.s_max)?;
+        for (workflow, rule) in &self.path {
+            write!(f, " {workflow}[{rule}]")?;
+        }
+
+        Ok(())
+    }
+}
+
+// Problems found by Program::analyze
+#[derive(Debug, PartialEq, Eq)]
+enum Issue {
+    // A rule sends parts to a workflow that isn't defined.  referenced_by is
+    // None when it's the starting workflow that's missing.
+    MissingWorkflow { name: String, referenced_by: Option<String> },
+    // Workflows that can send a part round in a loop, in name order.
+    Cycle(Vec<String>),
+    // A rule that no part can reach, or whose condition no part meets.
+    UnreachableRule { workflow: String, rule: usize },
+    // A workflow that accepts every part, or rejects every part.
+    ConstantOutcome { workflow: String, accepted: bool },
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Issue::MissingWorkflow { name, referenced_by: Some(from) } =>
+                write!(f, "{from} refers to missing workflow {name}"),
+            Issue::MissingWorkflow { name, referenced_by: None } =>
+                write!(f, "starting workflow {name} is missing"),
+            Issue::Cycle(names) =>
+                write!(f, "cycle between workflows {}", names.join(", ")),
+            Issue::UnreachableRule { workflow, rule } =>
+                write!(f, "rule {rule} of {workflow} is unreachable"),
+            Issue::ConstantOutcome { workflow, accepted: true } =>
+                write!(f, "{workflow} always accepts"),
+            Issue::ConstantOutcome { workflow, accepted: false } =>
+                write!(f, "{workflow} always rejects"),
+        }
+    }
+}
+
+// The workflows compiled into a table indexed by number, so that running a
+// part through them needs no name lookups.  Workflows are numbered in name
+// order.  Names that are referenced but never defined are numbered after
+// the defined ones, and have no rules.
+struct Program {
+    names: Vec<String>,
+    rules: Vec<Vec<(Condition, Target)>>,
+    defined: usize,
+    start: usize,
+}
+
+impl Program {
+    fn compile(workflows: &HashMap<String, WorkFlow>, start: &str) -> Program {
+        let mut names: Vec<String> = workflows.keys().cloned().collect();
+        names.sort();
+        let defined = names.len();
+        let mut numbers: HashMap<String, usize> = names.iter().cloned()
+            .enumerate()
+            .map(|(n, name)| (name, n))
+            .collect();
+
+        // Number of a workflow, adding it as a missing one if it's new.
+        let mut resolve = |name: &str, names: &mut Vec<String>| -> usize {
+            *numbers.entry(name.to_string()).or_insert_with(|| {
+                names.push(name.to_string());
+                names.len() - 1
+            })
+        };
+
+        let mut rules: Vec<Vec<(Condition, Target)>> = Vec::new();
+        for n in 0..defined {
+            let wf = &workflows[&names[n]];
+            let mut compiled: Vec<(Condition, Target)> = Vec::new();
+            for (cond, action) in &wf.steps {
+                let target = match action {
+                    Action::Accept => Target::Accept,
+                    Action::Reject => Target::Reject,
+                    Action::Continue(next_wf) => Target::Flow(resolve(next_wf, &mut names)),
+                };
+                compiled.push((*cond, target));
+            }
+            rules.push(compiled);
+        }
+
+        let start = resolve(start, &mut names);
+        rules.resize(names.len(), Vec::new());
+
+        Program { names, rules, defined, start }
+    }
+
+    // Where the workflow sends the part, if any rule applies.
+    fn next(&self, flow: usize, part: &Part) -> Option<Target> {
+        self.rules[flow].iter()
+            .find(|(cond, _)| cond.eval(part))
+            .map(|(_, target)| *target)
+    }
+
+    // Some(true) if accepted, Some(false) if rejected.  None if the part
+    // reaches a missing workflow, runs off the end of one, or goes round a
+    // cycle.
+    fn run(&self, part: &Part) -> Option<bool> {
+        let mut flow = self.start;
+
+        // Without a cycle, no part visits a workflow twice.
+        for _ in 0..self.names.len() {
+            match self.next(flow, part)? {
+                Target::Accept => return Some(true),
+                Target::Reject => return Some(false),
+                Target::Flow(next) => flow = next,
+            }
+        }
+
+        None
+    }
+
+    // Number of parts in the domain that the workflow accepts, or None if
+    // any of them would have no outcome (as in run).
+    fn accepted(&self, flow: usize, domain: Domain) -> Option<usize> {
+        let regions = self.accepted_regions(flow, domain)?;
+
+        Some(regions.iter().map(|region| region.domain.size()).sum())
+    }
+
+    // The parts in the domain that the workflow accepts, as boxes that don't
+    // overlap.  None if any of the parts would have no outcome.
+    fn accepted_regions(&self, flow: usize, domain: Domain) -> Option<Vec<Region>> {
+        let mut regions: Vec<Region> = Vec::new();
+        self.accepted_regions_(flow, domain, &mut Vec::new(), &mut regions)?;
+
+        Some(regions)
+    }
+
+    fn accepted_regions_(&self, flow: usize, domain: Domain, path: &mut Vec<(usize, usize)>,
+                         regions: &mut Vec<Region>) -> Option<()> {
+        if path.len() > self.names.len() {
+            // Must have gone round a cycle
+            return None;
+        }
+
+        // Each rule takes the parts that the rules before it didn't.
+        let mut rest = domain;
+        for (rule, (cond, target)) in self.rules[flow].iter().enumerate() {
+            if rest.is_empty() {
+                break;
+            }
+
+            let (f_domain, t_domain) = rest.split(cond);
+            if !t_domain.is_empty() {
+                path.push((flow, rule));
+                match target {
+                    Target::Accept => {
+                        let path = path.iter().map(|(wf, rule)| (self.names[*wf].clone(), *rule)).collect();
+                        regions.push(Region { domain: t_domain, path });
+                    }
+                    Target::Reject => {}
+                    Target::Flow(next) => self.accepted_regions_(*next, t_domain, path, regions)?,
+                }
+                path.pop();
+            }
+            rest = f_domain;
+        }
+
+        if rest.is_empty() {
+            Some(())
+        }
+        else {
+            None
+        }
+    }
+
+    // The workflows each workflow can send parts to, directly or not.
+    fn reachable(&self) -> Vec<BitSet> {
+        let n = self.names.len();
+        let mut reach: Vec<BitSet> = Vec::new();
+
+        for flow in 0..n {
+            let mut seen = BitSet::new(n);
+            let mut stack = vec![flow];
+            while let Some(from) = stack.pop() {
+                for (_, target) in &self.rules[from] {
+                    if let Target::Flow(next) = target {
+                        if seen.insert(*next) {
+                            stack.push(*next);
+                        }
+                    }
+                }
+            }
+            reach.push(seen);
+        }
+
+        reach
+    }
+
+    // Problems with the workflows, for parts with ratings in bounds
+    fn analyze(&self, bounds: Domain) -> Vec<Issue> {
+        let mut issues: Vec<Issue> = Vec::new();
+
+        // References to workflows that don't exist
+        if self.start >= self.defined {
+            issues.push(Issue::MissingWorkflow { name: self.names[self.start].clone(), referenced_by: None });
+        }
+        for flow in 0..self.defined {
+            for (_, target) in &self.rules[flow] {
+                if let Target::Flow(next) = target {
+                    if *next >= self.defined {
+                        issues.push(Issue::MissingWorkflow {
+                            name: self.names[*next].clone(),
+                            referenced_by: Some(self.names[flow].clone()),
+                        });
+                    }
+                }
+            }
+        }
+
+        // Cycles: groups of workflows that all reach each other.  Each group
+        // is reported once, by its first member.
+        let reach = self.reachable();
+        for flow in 0..self.defined {
+            if !reach[flow].contains(flow) {
+                continue;
+            }
+            let group: Vec<usize> = reach[flow].iter()
+                .filter(|other| reach[*other].contains(flow))
+                .collect();
+            if group[0] == flow {
+                issues.push(Issue::Cycle(group.iter().map(|n| self.names[*n].clone()).collect()));
+            }
+        }
+
+        // Rules that can't apply to any part
+        for flow in 0..self.defined {
+            let mut rest = bounds;
+            for (rule, (cond, _)) in self.rules[flow].iter().enumerate() {
+                let (f_domain, t_domain) = rest.split(cond);
+                if t_domain.is_empty() {
+                    issues.push(Issue::UnreachableRule { workflow: self.names[flow].clone(), rule });
+                }
+                rest = f_domain;
+            }
+        }
+
+        // Workflows whose outcome doesn't depend on the part
+        let all = bounds.size();
+        for flow in 0..self.defined {
+            let accepted = match self.accepted(flow, bounds) {
+                Some(0) => false,
+                Some(n) if n == all => true,
+                _ => continue,
+            };
+            issues.push(Issue::ConstantOutcome { workflow: self.names[flow].clone(), accepted });
+        }
+
+        issues
+    }
 }
 
 struct Input {
-    workflows: HashMap<String, WorkFlow>,
+    program: Program,
     parts: Vec<Part>,
 }
 
@@ -304,42 +647,13 @@ impl Input {
             }
         }
 
-        Input { workflows, parts }
+        let program = Program::compile(&workflows, "in");
+
+        Input { program, parts }
     }
 
     fn part_passes(&self, part: &Part) -> bool {
-        // Evaluate whether a part passes
-        let pass;
-        let mut wf = self.workflows.get("in").unwrap();
-        let mut index = 0;
-
-        loop {
-            // Test condition for current wf, current index
-            let (cond, action) = &wf.steps[index];
-            if cond.eval(part) {
-                // Do this action
-                match action {
-                    Action::Accept => {
-                        pass = true;
-                        break;
-                    }
-                    Action::Reject => {
-                        pass = false;
-                        break;
-                    }
-                    Action::Continue(next_wf) => {
-                        index = 0;
-                        wf = self.workflows.get(next_wf).unwrap();
-                    }
-                }
-            }
-            else {
-                index += 1;
-            }
-
-        }
-        
-        pass
+        self.program.run(part).unwrap_or_else(|| self.no_outcome(Domain::new()))
     }
 
     fn rating_sum(&self) -> usize {
@@ -349,48 +663,31 @@ impl Input {
             .sum()
     }
 
-    fn combos_(&self, wf_name: &str, index: usize, domain: Domain) -> usize {
-        let wf = self.workflows.get(wf_name).unwrap();
-        let (cond, action) = &wf.steps[index];
-
-        let combos = match cond {
-            Condition::True => {
-                // A
-                // No split, just pursue the action
-                match &action {
-                    Action::Accept => domain.size(),
-                    Action::Reject => 0,
-                    Action::Continue(next_wf) => {
-                        self.combos_(next_wf, 0, domain)
-                    }
-                }
-            }
-            _ => {
-                // B
-                let (f_domain, t_domain) = domain.split(&cond);
-                // Evaluate size when condition is true and we take the action
-                let t_size = match &action {
-                    Action::Accept => t_domain.size(),
-                    Action::Reject => 0,
-                    Action::Continue(next_wf) => {
-                        self.combos_(next_wf, 0, t_domain)
-                    }
-                };
-
-                // Evaluate size when condition is false and we don't take the action
-                let f_size = self.combos_(wf_name, index+1, f_domain);
+    fn combos(&self) -> usize {
+        self.combos_within(Domain::new())
+    }
 
-                t_size + f_size
-            }
-        };
+    // Number of accepted parts with ratings in the domain
+    fn combos_within(&self, domain: Domain) -> usize {
+        self.program.accepted(self.program.start, domain).unwrap_or_else(|| self.no_outcome(domain))
+    }
 
-        combos
+    // The accepted parts with ratings in the domain, e.g. the parts with
+    // x=1000 are Domain::new().restrict(Category::X, 1000, 1000)
+    #[cfg(test)]
+    fn accepted_regions(&self, domain: Domain) -> Vec<Region> {
+        self.program.accepted_regions(self.program.start, domain).unwrap_or_else(|| self.no_outcome(domain))
     }
 
-    fn combos(&self) -> usize {
-        let domain = Domain::new();
+    fn analyze(&self, bounds: Domain) -> Vec<Issue> {
+        self.program.analyze(bounds)
+    }
 
-        self.combos_("in", 0, domain)
+    // For parts in bounds that get no outcome: panics with what's wrong with
+    // the workflows.
+    fn no_outcome(&self, bounds: Domain) -> ! {
+        let issues: Vec<String> = self.analyze(bounds).iter().map(|issue| issue.to_string()).collect();
+        panic!("Some parts have no outcome: {}", issues.join("; "))
     }
 }
 
@@ -420,12 +717,23 @@ impl<'a> Day for Day19<'a> {
 
 #[cfg(test)]
 mod test {
-    use crate::{day19::{Input, Day19}, day::{Answer, Day}};
+    use std::collections::HashMap;
+
+    use crate::{day19::{Input, Day19, WorkFlow, Part, Program, Issue, Domain, Category}, day::{Answer, Day}};
+
+    fn program(lines: &[&str]) -> Program {
+        let workflows: HashMap<String, WorkFlow> = lines.iter()
+            .map(|line| WorkFlow::from_str(line).unwrap())
+            .map(|wf| (wf.name.clone(), wf))
+            .collect();
+
+        Program::compile(&workflows, "in")
+    }
 
     #[test]
     fn test_input() {
         let input = Input::read("examples/day19_example1.txt");
-        assert_eq!(input.workflows.len(), 11);
+        assert_eq!(input.program.defined, 11);
         assert_eq!(input.parts.len(), 5);
     }
 
@@ -471,6 +779,141 @@ mod test {
         assert_eq!(input.combos(), 167409079868000);
     }
 
+    #[test]
+    fn test_compile() {
+        let input = Input::read("examples/day19_example1.txt");
+        let program = &input.program;
+
+        assert_eq!(program.names.len(), 11);
+        assert_eq!(program.defined, 11);
+        assert_eq!(program.names[program.start], "in");
+
+        let part = Part::from_str("{x=787,m=2655,a=1222,s=2876}").unwrap();
+        assert_eq!(program.run(&part), Some(true));
+    }
+
+    #[test]
+    fn test_analyze_example() {
+        let input = Input::read("examples/day19_example1.txt");
+
+        assert_eq!(input.analyze(Domain::new()), vec![
+            Issue::ConstantOutcome { workflow: "gd".to_string(), accepted: false },
+            Issue::ConstantOutcome { workflow: "lnx".to_string(), accepted: true },
+            Issue::ConstantOutcome { workflow: "qs".to_string(), accepted: true },
+        ]);
+    }
+
+    #[test]
+    fn test_analyze_problems() {
+        let program = program(&[
+            "in{x<100:ab,m>10:cd,R}",
+            "ab{x<50:A,x<20:R,a<1:A,zz}",
+            "cd{s>100:ab,ef}",
+            "ef{a>5:cd,A}",
+        ]);
+        let issues = program.analyze(Domain::new());
+
+        assert_eq!(issues, vec![
+            Issue::MissingWorkflow { name: "zz".to_string(), referenced_by: Some("ab".to_string()) },
+            Issue::Cycle(vec!["cd".to_string(), "ef".to_string()]),
+            Issue::UnreachableRule { workflow: "ab".to_string(), rule: 1 },
+            Issue::UnreachableRule { workflow: "ab".to_string(), rule: 2 },
+        ]);
+        assert_eq!(issues[0].to_string(), "ab refers to missing workflow zz");
+        assert_eq!(issues[1].to_string(), "cycle between workflows cd, ef");
+
+        // Parts that hit a problem have no outcome, rather than a panic or a hang.
+        let part = |text: &str| Part::from_str(text).unwrap();
+        assert_eq!(program.run(&part("{x=10,m=1,a=1,s=1}")), Some(true));
+        assert_eq!(program.run(&part("{x=60,m=1,a=1,s=1}")), None);
+        assert_eq!(program.run(&part("{x=200,m=20,a=10,s=1}")), None);
+        assert_eq!(program.run(&part("{x=200,m=20,a=1,s=1}")), Some(true));
+        assert_eq!(program.accepted(program.start, Domain::new()), None);
+    }
+
+    #[test]
+    fn test_missing_start() {
+        let program = program(&["ab{A}"]);
+
+        assert_eq!(program.analyze(Domain::new()), vec![
+            Issue::MissingWorkflow { name: "in".to_string(), referenced_by: None },
+            Issue::ConstantOutcome { workflow: "ab".to_string(), accepted: true },
+        ]);
+        assert_eq!(program.run(&Part::from_str("{x=1,m=1,a=1,s=1}").unwrap()), None);
+    }
+
+    #[test]
+    fn test_analyze_bounds() {
+        let program = program(&["in{x>50:R,A}"]);
+
+        assert_eq!(program.analyze(Domain::new()), vec![]);
+        assert_eq!(program.analyze(Domain::with_bounds(1, 10)), vec![
+            Issue::UnreachableRule { workflow: "in".to_string(), rule: 0 },
+            Issue::ConstantOutcome { workflow: "in".to_string(), accepted: true },
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Some parts have no outcome: ab refers to missing workflow zz")]
+    fn test_no_outcome() {
+        let input = Input { program: program(&["in{x<100:ab,A}", "ab{zz}"]), parts: Vec::new() };
+
+        input.combos();
+    }
+
+    #[test]
+    fn test_accepted_regions() {
+        let input = Input::read("examples/day19_example1.txt");
+        let regions = input.accepted_regions(Domain::new());
+
+        let total: usize = regions.iter().map(|r| r.domain.size()).sum();
+        assert_eq!(total, 167409079868000);
+
+        // No part is in two regions.
+        for (i, a) in regions.iter().enumerate() {
+            for b in &regions[i+1..] {
+                assert!(a.domain.intersect(&b.domain).is_empty());
+            }
+        }
+
+        // A part is accepted exactly when some region holds it, and the
+        // region's path is the route it takes.
+        for part in &input.parts {
+            let holding: Vec<_> = regions.iter().filter(|r| r.domain.contains(part)).collect();
+            assert_eq!(holding.len(), input.part_passes(part) as usize);
+        }
+        let region = regions.iter().find(|r| r.domain.contains(&input.parts[0])).unwrap();
+        let route: Vec<&str> = region.path.iter().map(|(wf, _)| wf.as_str()).collect();
+        assert_eq!(route, vec!["in", "qqz", "qs", "lnx"]);
+
+        assert_eq!(regions[0].to_string(), "x=1..=1415 m=1..=4000 a=1..=2005 s=1..=1350 via in[0] px[0] qkq[0]");
+    }
+
+    #[test]
+    fn test_region_queries() {
+        let input = Input::read("examples/day19_example1.txt");
+
+        // Which ratings are accepted for x=1000?
+        let x_1000 = Domain::new().restrict(Category::X, 1000, 1000);
+        let regions = input.accepted_regions(x_1000);
+        assert!(regions.iter().all(|r| r.domain.range(Category::X) == (1000, 1000)));
+        assert_eq!(regions.iter().map(|r| r.domain.size()).sum::<usize>(), input.combos_within(x_1000));
+
+        // The per-x counts add up to the whole.
+        let mut sum = 0;
+        for (lo, hi) in [(1, 1415), (1416, 2440), (2441, 2662), (2663, 4000)] {
+            sum += input.combos_within(Domain::new().restrict(Category::X, lo, hi));
+        }
+        assert_eq!(sum, input.combos());
+
+        // Smaller bounds
+        let small = Domain::with_bounds(1, 10);
+        assert_eq!(small.size(), 10_000);
+        let regions = input.accepted_regions(small);
+        assert!(regions.iter().all(|r| r.domain.intersect(&small) == r.domain));
+        assert_eq!(input.combos_within(small), regions.iter().map(|r| r.domain.size()).sum::<usize>());
+    }
+
     #[test]
     fn test_part2() {
         let d = Day19::new("examples/day19_example1.txt");
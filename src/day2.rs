@@ -1,47 +1,96 @@
-use std::fs::File;
+use std::{collections::BTreeMap, fs::File};
 
 use crate::day::{Day, Answer, LineBasedInput};
 
-struct Play {
-    red: usize,
-    green: usize,
-    blue: usize,
+// Counts of cubes by colour.  Each play is one of these, and so is what's in
+// a bag.  Colours that aren't mentioned count as zero.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Cubes {
+    counts: BTreeMap<String, usize>,
 }
 
-impl Play {
-    fn possible(&self) -> bool {
-        self.red <= 12 && self.green <= 13 && self.blue <= 14
+// The colours the power of a set of cubes is taken over
+const COLORS: [&str; 3] = ["red", "green", "blue"];
+
+impl Cubes {
+    fn new(counts: &[(&str, usize)]) -> Cubes {
+        Cubes { counts: counts.iter().map(|(color, n)| (color.to_string(), *n)).collect() }
+    }
+
+    // The bag from part 1
+    fn puzzle_bag() -> Cubes {
+        Cubes::new(&[("red", 12), ("green", 13), ("blue", 14)])
+    }
+
+    fn get(&self, color: &str) -> usize {
+        self.counts.get(color).copied().unwrap_or(0)
+    }
+
+    // Whether these cubes could all have come out of the bag at once
+    fn fits_in(&self, bag: &Cubes) -> bool {
+        self.counts.iter().all(|(color, n)| *n <= bag.get(color))
+    }
+
+    // Raise each colour's count to at least other's.
+    fn include(&mut self, other: &Cubes) {
+        for (color, n) in &other.counts {
+            let count = self.counts.entry(color.clone()).or_insert(0);
+            *count = (*count).max(*n);
+        }
+    }
+
+    // Product of the counts of the given colours
+    fn power_of(&self, colors: &[&str]) -> usize {
+        colors.iter().map(|color| self.get(color)).product()
     }
 }
 
+// A draw that shows more of a colour than the bag holds
+#[cfg(test)]
+#[derive(Debug, PartialEq, Eq)]
+struct Violation {
+    play: usize,
+    color: String,
+    shown: usize,
+    available: usize,
+}
+
 struct Game {
     game_no: usize,
-    plays: Vec<Play>
+    plays: Vec<Cubes>
 }
 
 impl Game {
     fn possible(&self) -> bool {
-        self.plays.iter().fold(true, |summary, play| summary && play.possible())
+        self.possible_with(&Cubes::puzzle_bag())
     }
 
-    fn power(&self) -> usize {
-        let mut min_red = 0;
-        let mut min_green = 0;
-        let mut min_blue = 0;
+    fn possible_with(&self, bag: &Cubes) -> bool {
+        self.plays.iter().all(|play| play.fits_in(bag))
+    }
 
+    // The fewest cubes of each colour the bag could have held
+    fn min_bag(&self) -> Cubes {
+        let mut bag = Cubes::default();
         for play in &self.plays {
-            if play.red > min_red {
-                min_red = play.red;
-            }
-            if play.green > min_green {
-                min_green = play.green;
-            }
-            if play.blue > min_blue {
-                min_blue = play.blue;
-            }
+            bag.include(play);
         }
 
-        min_red * min_green * min_blue
+        bag
+    }
+
+    // Every colour in every play that the bag doesn't hold enough of
+    #[cfg(test)]
+    fn violations(&self, bag: &Cubes) -> Vec<Violation> {
+        self.plays.iter().enumerate()
+            .flat_map(|(play_no, play)| play.counts.iter()
+                .filter(|(color, n)| **n > bag.get(color))
+                .map(move |(color, n)| Violation { play: play_no, color: color.clone(), shown: *n, available: bag.get(color) }))
+            .collect()
+    }
+
+    fn power(&self) -> usize {
+        self.min_bag().power_of(&COLORS)
     }
 }
 
@@ -49,6 +98,40 @@ struct Input {
     games: Vec<Game>,
 }
 
+// Queries over all the games
+#[cfg(test)]
+impl Input {
+    // Numbers of the games that could have been played with this bag
+    fn possible_games(&self, bag: &Cubes) -> Vec<usize> {
+        self.games.iter()
+            .filter(|game| game.possible_with(bag))
+            .map(|game| game.game_no)
+            .collect()
+    }
+
+    // The smallest bag every game could have been played with
+    fn min_bag(&self) -> Cubes {
+        let mut bag = Cubes::default();
+        for game in &self.games {
+            bag.include(&game.min_bag());
+        }
+
+        bag
+    }
+
+    // All the colours seen, in name order
+    fn colors(&self) -> Vec<&str> {
+        let mut colors: Vec<&str> = self.games.iter()
+            .flat_map(|game| game.plays.iter())
+            .flat_map(|play| play.counts.keys().map(|color| color.as_str()))
+            .collect();
+        colors.sort();
+        colors.dedup();
+
+        colors
+    }
+}
+
 pub struct Day2<'a>{
     input_filename: &'a str,
 }
@@ -80,9 +163,9 @@ impl<'a> LineBasedInput<Game> for Day2<'a> {
         // "6 red, 1 blue ...", "2 blue, 1 red, ...";
         let split2 = split1[1].split(";"); 
 
-        let mut plays: Vec<Play> = Vec::new();
+        let mut plays: Vec<Cubes> = Vec::new();
         for play_str in split2 {
-            let mut play = Play { red: 0, green: 0, blue: 0 };
+            let mut play = Cubes::default();
 
             // Split into "6 red"
             for show_str in play_str.split(",") {
@@ -91,12 +174,7 @@ impl<'a> LineBasedInput<Game> for Day2<'a> {
                 let n = split3[0].parse::<usize>().unwrap();
                 let color = split3[1];
 
-                match color {
-                    "red" => play.red = n,
-                    "green" => play.green = n,
-                    "blue" => play.blue = n,
-                    _ => panic!(),
-                }
+                play.counts.insert(color.to_string(), n);
             }
 
             plays.push(play);
@@ -134,7 +212,8 @@ impl<'a> Day for Day2<'a> {
 #[cfg(test)]
 mod tests {
     use crate::{Day, Answer, Day2};
-    use crate::day2::Play;
+    use crate::day2::{Cubes, Game, Violation, COLORS};
+    use crate::day::LineBasedInput;
 
     #[test]
     fn test_input_p1() {
@@ -154,17 +233,17 @@ mod tests {
 
     #[test]
     fn test_plays() {
-        let p1 = Play {red: 12, green: 13, blue: 14};
-        assert!(p1.possible());
+        let p1 = Cubes::new(&[("red", 12), ("green", 13), ("blue", 14)]);
+        assert!(p1.fits_in(&Cubes::puzzle_bag()));
 
-        let p2 = Play {red: 13, green: 13, blue: 14};
-        assert!(!p2.possible());
+        let p2 = Cubes::new(&[("red", 13), ("green", 13), ("blue", 14)]);
+        assert!(!p2.fits_in(&Cubes::puzzle_bag()));
 
-        let p3 = Play {red: 12, green: 14, blue: 14};
-        assert!(!p3.possible());
+        let p3 = Cubes::new(&[("red", 12), ("green", 14), ("blue", 14)]);
+        assert!(!p3.fits_in(&Cubes::puzzle_bag()));
 
-        let p4 = Play {red: 12, green: 13, blue: 15};
-        assert!(!p4.possible());
+        let p4 = Cubes::new(&[("red", 12), ("green", 13), ("blue", 15)]);
+        assert!(!p4.fits_in(&Cubes::puzzle_bag()));
     }
 
     #[test]
@@ -191,4 +270,56 @@ mod tests {
         let d = Day2::new("examples/day2_example1.txt");
         assert_eq!(d.part2(), Answer::Numeric(2286));
     }
+
+    #[test]
+    fn test_min_bag() {
+        let d = Day2::new("examples/day2_example1.txt");
+        let input = d.read_input(true);
+
+        assert_eq!(input.games[0].min_bag(), Cubes::new(&[("red", 4), ("green", 2), ("blue", 6)]));
+        assert_eq!(input.min_bag(), Cubes::new(&[("red", 20), ("green", 13), ("blue", 15)]));
+        assert_eq!(input.colors(), vec!["blue", "green", "red"]);
+
+        // Every game fits in the smallest bag for all of them, and taking one
+        // cube of any colour out of it leaves some game that doesn't fit.
+        let bag = input.min_bag();
+        assert_eq!(input.possible_games(&bag), vec![1, 2, 3, 4, 5]);
+        for (color, expected) in COLORS.iter().zip([vec![1, 2, 4, 5], vec![1, 2, 4, 5], vec![1, 2, 3, 5]]) {
+            let mut smaller = bag.clone();
+            *smaller.counts.get_mut(*color).unwrap() -= 1;
+            assert_eq!(input.possible_games(&smaller), expected, "one fewer {color}");
+        }
+        assert_eq!(input.possible_games(&Cubes::puzzle_bag()), vec![1, 2, 5]);
+        assert_eq!(input.possible_games(&Cubes::new(&[("red", 20), ("green", 13), ("blue", 14)])), vec![1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn test_violations() {
+        let d = Day2::new("examples/day2_example1.txt");
+        let input = d.read_input(true);
+        let bag = Cubes::puzzle_bag();
+
+        assert_eq!(input.games[0].violations(&bag), vec![]);
+        assert_eq!(input.games[2].violations(&bag), vec![
+            Violation { play: 0, color: "red".to_string(), shown: 20, available: 12 },
+        ]);
+        assert_eq!(input.games[3].violations(&bag), vec![
+            Violation { play: 2, color: "blue".to_string(), shown: 15, available: 14 },
+            Violation { play: 2, color: "red".to_string(), shown: 14, available: 12 },
+        ]);
+    }
+
+    #[test]
+    fn test_other_colors() {
+        let game: Game = Day2::parse_line("Game 7: 2 red, 4 purple; 1 teal, 3 purple", false).unwrap();
+
+        assert_eq!(game.min_bag(), Cubes::new(&[("purple", 4), ("red", 2), ("teal", 1)]));
+        assert!(!game.possible());
+        assert!(game.possible_with(&Cubes::new(&[("red", 2), ("purple", 5), ("teal", 1)])));
+        assert_eq!(game.violations(&Cubes::puzzle_bag()).len(), 3);
+
+        // Power is over red, green and blue, and there's no green.
+        assert_eq!(game.power(), 0);
+        assert_eq!(game.min_bag().power_of(&["purple", "teal"]), 4);
+    }
 }
@@ -1,19 +1,199 @@
-use std::{collections::{HashMap, VecDeque}, fs::File, io::{BufRead, BufReader}};
+use std::{collections::{HashMap, VecDeque}, fmt::{Display, Error, Formatter}, fs::File, io::{BufRead, BufReader}};
 
 use crate::day::{Day, Answer};
+use crate::cycle::Detector;
+use crate::bitset::BitSet;
+use num::integer::{ExtendedGcd, Integer};
 
 use lazy_static::lazy_static;
 use regex::Regex;
 
 lazy_static! {
-    static ref LINE_RE: Regex = Regex::new("([%&]?)([a-z]+) -> (.*)").unwrap();
+    // caps[1]: prefix, if any, caps[2]: optional number after the prefix,
+    // caps[3]: name, caps[4]: outputs
+    static ref LINE_RE: Regex = Regex::new("^(?:([^a-z0-9\\s])([0-9]*))?([a-z]+) -> ([a-z]+(?:, [a-z]+)*)$").unwrap();
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum ModType {
     Node,
     FlipFlop,
     Nand,
+    // Any other prefix, with the number after it (if any).  Its behaviour
+    // comes from the Kinds registered with the simulator.
+    Custom { prefix: char, arg: Option<usize> },
+}
+
+// How a module responds to pulses.
+trait Behavior {
+    // Name of this kind of module, for pulse accounting
+    fn kind(&self) -> &'static str;
+
+    // Handles a pulse from module `from`.  inputs holds the last pulse seen
+    // from each input, including this one.  Returns the pulse to send to
+    // every output, if any.
+    fn receive(&mut self, from: usize, high: bool, inputs: &HashMap<usize, bool>) -> Option<bool>;
+
+    // Any state kept beyond the last pulse from each input, for cycle
+    // detection.
+    fn state_bits(&self) -> Vec<bool> {
+        Vec::new()
+    }
+}
+
+// Passes every pulse on: the broadcaster, the button, and output-only modules.
+struct Broadcast;
+
+impl Behavior for Broadcast {
+    fn kind(&self) -> &'static str {
+        "node"
+    }
+
+    fn receive(&mut self, _from: usize, high: bool, _inputs: &HashMap<usize, bool>) -> Option<bool> {
+        Some(high)
+    }
+}
+
+// Ignores high pulses.  A low pulse switches it on or off, and it sends high
+// when it switches on.
+struct Toggle {
+    on: bool,
+}
+
+impl Behavior for Toggle {
+    fn kind(&self) -> &'static str {
+        "flip-flop"
+    }
+
+    fn receive(&mut self, _from: usize, high: bool, _inputs: &HashMap<usize, bool>) -> Option<bool> {
+        if high {
+            None
+        }
+        else {
+            self.on = !self.on;
+            Some(self.on)
+        }
+    }
+
+    fn state_bits(&self) -> Vec<bool> {
+        vec![self.on]
+    }
+}
+
+// Sends a pulse every time it receives one, computed from the last pulse
+// seen from each input: rule(high inputs, all inputs).
+#[derive(Clone, Copy)]
+struct Gate {
+    kind: &'static str,
+    rule: fn(usize, usize) -> bool,
+}
+
+impl Gate {
+    const NAND: Gate = Gate { kind: "conjunction", rule: |high, all| high < all };
+    const AND: Gate = Gate { kind: "and", rule: |high, all| high == all };
+    const OR: Gate = Gate { kind: "or", rule: |high, _| high > 0 };
+    const XOR: Gate = Gate { kind: "xor", rule: |high, _| high % 2 == 1 };
+}
+
+impl Behavior for Gate {
+    fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    fn receive(&mut self, _from: usize, _high: bool, inputs: &HashMap<usize, bool>) -> Option<bool> {
+        let high = inputs.values().filter(|pulse| **pulse).count();
+        Some((self.rule)(high, inputs.len()))
+    }
+}
+
+// Divides by `every`: passes on every every-th low pulse, and ignores the
+// rest, along with all high pulses.
+struct Counter {
+    every: usize,
+    count: usize,
+}
+
+impl Behavior for Counter {
+    fn kind(&self) -> &'static str {
+        "counter"
+    }
+
+    fn receive(&mut self, _from: usize, high: bool, _inputs: &HashMap<usize, bool>) -> Option<bool> {
+        if high {
+            return None;
+        }
+
+        self.count = (self.count + 1) % self.every;
+        if self.count == 0 { Some(false) } else { None }
+    }
+
+    fn state_bits(&self) -> Vec<bool> {
+        (0..usize::BITS - self.every.leading_zeros()).map(|bit| self.count >> bit & 1 == 1).collect()
+    }
+}
+
+// A delay line: answers each pulse with the one it received `length`
+// pulses earlier, starting out full of low pulses.
+struct Delay {
+    line: VecDeque<bool>,
+}
+
+impl Behavior for Delay {
+    fn kind(&self) -> &'static str {
+        "delay"
+    }
+
+    fn receive(&mut self, _from: usize, high: bool, _inputs: &HashMap<usize, bool>) -> Option<bool> {
+        self.line.push_back(high);
+        self.line.pop_front()
+    }
+
+    fn state_bits(&self) -> Vec<bool> {
+        self.line.iter().copied().collect()
+    }
+}
+
+// Makes the behaviour for a module, given the number after its prefix.
+type MakeBehavior = fn(Option<usize>) -> Box<dyn Behavior>;
+
+// The module types the simulator knows about beyond the puzzle's own
+// (broadcast, % and &), by prefix.
+struct Kinds {
+    kinds: HashMap<char, MakeBehavior>,
+}
+
+impl Kinds {
+    // No extra kinds
+    fn new() -> Kinds {
+        Kinds { kinds: HashMap::new() }
+    }
+
+    // *and, |or, ^xor, #3counter (divide by 3) and ~2delay (2 pulses)
+    fn standard() -> Kinds {
+        let mut kinds = Kinds::new();
+        kinds.register('*', |_| Box::new(Gate::AND));
+        kinds.register('|', |_| Box::new(Gate::OR));
+        kinds.register('^', |_| Box::new(Gate::XOR));
+        kinds.register('#', |arg| Box::new(Counter { every: arg.unwrap_or(2).max(1), count: 0 }));
+        kinds.register('~', |arg| Box::new(Delay { line: VecDeque::from(vec![false; arg.unwrap_or(1)]) }));
+
+        kinds
+    }
+
+    fn register(&mut self, prefix: char, make: MakeBehavior) {
+        self.kinds.insert(prefix, make);
+    }
+
+    // The behaviour for a module of this type, or None if its prefix isn't
+    // registered.
+    fn make(&self, mod_type: ModType) -> Option<Box<dyn Behavior>> {
+        match mod_type {
+            ModType::Node => Some(Box::new(Broadcast)),
+            ModType::FlipFlop => Some(Box::new(Toggle { on: false })),
+            ModType::Nand => Some(Box::new(Gate::NAND)),
+            ModType::Custom { prefix, arg } => self.kinds.get(&prefix).map(|make| make(arg)),
+        }
+    }
 }
 
 struct Input {
@@ -37,18 +217,22 @@ impl Input {
 
     fn process_line(s: &str) -> Option<(ModType, String, Vec<String>)> {
         if let Some(caps) = LINE_RE.captures(s) {
-            // Module type: caps[1]
-            let mod_type = match &caps[1] {
-                "%" => ModType::FlipFlop,
-                "&" => ModType::Nand,
-                _ => ModType::Node,
+            // Module type: caps[1], caps[2]
+            let mod_type = match caps.get(1).map(|prefix| prefix.as_str()) {
+                Some("%") => ModType::FlipFlop,
+                Some("&") => ModType::Nand,
+                None => ModType::Node,
+                Some(prefix) => ModType::Custom {
+                    prefix: prefix.chars().next().unwrap(),
+                    arg: caps[2].parse::<usize>().ok(),
+                },
             };
 
-            // Module name: caps[2]
-            let mod_name = caps[2].to_string();
+            // Module name: caps[3]
+            let mod_name = caps[3].to_string();
 
-            // Module outputs: caps[3]
-            let outputs: Vec<String> = caps[3].split(", ").map(|s| s.to_string()).collect();
+            // Module outputs: caps[4]
+            let outputs: Vec<String> = caps[4].split(", ").map(|s| s.to_string()).collect();
 
             Some( (mod_type, mod_name, outputs) )
         }
@@ -60,17 +244,13 @@ impl Input {
 }
 
 
-#[derive(PartialEq)]
 struct Module {
     // module type
     mod_type: ModType,
-
-    // name
-    name: String,
+    behavior: Box<dyn Behavior>,
 
     // id
     id: usize,
-    mask: u128,
 
     // inputs: name of input and last pulse polarity seen.
     inputs: HashMap<usize, bool>,
@@ -83,12 +263,11 @@ struct Module {
 }
 
 impl Module {
-    pub fn new(mod_type: ModType, name: &str, id: usize) -> Module {
+    pub fn new(mod_type: ModType, id: usize, behavior: Box<dyn Behavior>) -> Module {
         Module { 
             mod_type, 
-            name: name.to_string(), 
+            behavior,
             id, 
-            mask: 1 << id, 
             inputs: HashMap::new(), 
             outputs: Vec::new(), 
             state: false 
@@ -105,15 +284,76 @@ impl Module {
     }
 }
 
+// A moment during a press: the depth and root of the pulse being delivered
+// (see Pulse).  Pulses in separate sub-networks never share a root, so
+// ordering moments orders their deliveries.
+type Moment = (usize, usize);
+
+// The moments from the first up to, but not including, the second.
+type Span = (Moment, Moment);
+
+// Before any pulse of a press, and after the last.
+const PRESS_START: Moment = (0, 0);
+const PRESS_END: Moment = (usize::MAX, usize::MAX);
+
+// The spans that are in both lists
+fn overlap(a: &[Span], b: &[Span]) -> Vec<Span> {
+    a.iter()
+        .flat_map(|(a_from, a_to)| b.iter().map(move |(b_from, b_to)| (*a_from.max(b_from), *a_to.min(b_to))))
+        .filter(|(from, to)| from < to)
+        .collect()
+}
+
+// When a sub-network's output is active, once its behaviour repeats: during
+// the spans of the presses in `early`, then from press `start` on, at every
+// press whose distance from start (modulo period) is in `offsets`.
+#[derive(Debug, PartialEq, Eq)]
+struct Activity {
+    early: Vec<(usize, Vec<Span>)>,
+    start: usize,
+    period: usize,
+    offsets: Vec<(usize, Vec<Span>)>,
+}
+
+impl Activity {
+    fn spans(&self, press: usize) -> &[Span] {
+        let found = if press < self.start {
+            self.early.iter().find(|(early, _)| *early == press)
+        }
+        else {
+            let offset = (press - self.start) % self.period;
+            self.offsets.iter().find(|(known, _)| *known == offset)
+        };
+
+        match found {
+            Some((_, spans)) => spans,
+            None => &[],
+        }
+    }
+
+    #[cfg(test)]
+    fn is_active(&self, press: usize) -> bool {
+        !self.spans(press).is_empty()
+    }
+}
+
+// Tracks the modules feeding one watched module: the flipflops and
+// conjunctions upstream of it, and when the last pulse the watched module
+// sent had the active polarity.
 struct StateSet {
     watched: usize,
     active_state: bool,
     modules: Vec<usize>,
-    observations: HashMap<u128, usize>,  // state -> step when first observed
+    nands: Vec<usize>,
+    others: Vec<usize>,
+    detector: Detector<BitSet>,
     steps: usize,
-    period: usize,
-    last_active: usize,
-    active_times: Vec<usize>,
+    // When the current span of activity began, if the module is active
+    active_since: Option<Moment>,
+    // The spans of activity so far in the current press
+    press_spans: Vec<Span>,
+    // Each press with any activity, and its spans
+    active_times: Vec<(usize, Vec<Span>)>,
 }
 
 impl StateSet {
@@ -122,6 +362,8 @@ impl StateSet {
         let mut to_visit: Vec<usize> = Vec::new();
         // TODO-DW : remove // let mut module_names: Vec<String> = Vec::new();
         let mut modules: Vec<usize> = Vec::new();
+        let mut nands: Vec<usize> = Vec::new();
+        let mut others: Vec<usize> = Vec::new();
 
         to_visit.push(m);
         while to_visit.len() > 0 {
@@ -141,6 +383,16 @@ impl StateSet {
                     modules.push(visiting_id);
                 }
 
+                // conjunctions remember their inputs, which is state too
+                if visiting.mod_type == ModType::Nand {
+                    nands.push(visiting_id);
+                }
+
+                // other kinds may keep state of their own
+                if matches!(visiting.mod_type, ModType::Custom { .. }) {
+                    others.push(visiting_id);
+                }
+
                 // push all inputs to to_visit
                 for input_id in visiting.inputs.keys() {
                     // println!("schedule visit to {}", input_name);
@@ -152,78 +404,262 @@ impl StateSet {
             }
         }
 
-        let observations: HashMap<u128, usize> = HashMap::new();
+        let mut state_set = StateSet {
+            watched: m, active_state, modules, nands, others,
+            detector: Detector::new(), steps: 0,
+            active_since: (sim_modules[m].state == active_state).then_some(PRESS_START),
+            press_spans: Vec::new(), active_times: Vec::new()
+        };
 
-        StateSet { watched: m, active_state, modules, observations, steps: 0, period: 0, last_active: 0, active_times: Vec::new() }
+        // Step 0 is the state before any presses.
+        let state = state_set.construct_state(sim_modules);
+        state_set.detector.observe(state);
+
+        state_set
     }
 
-    // the state of a state set.
-    fn construct_state(&self, modules: &Vec<Module>) -> u128 {
-        let mut state: u128 = 0;
-        for module_id in &self.modules {
-            let module = &modules[*module_id];
-            if module.state {
-                state |= module.mask;
+    // the state of a state set: the state of each flipflop, then the last
+    // pulse each conjunction saw from each of its inputs, then the state of
+    // any other kinds of module, then the last pulse the watched module sent.
+    fn construct_state(&self, modules: &[Module]) -> BitSet {
+        let other_bits: Vec<bool> = self.others.iter()
+            .flat_map(|id| {
+                let module = &modules[*id];
+                let mut inputs: Vec<(&usize, &bool)> = module.inputs.iter().collect();
+                inputs.sort();
+                inputs.into_iter().map(|(_, pulse)| *pulse).chain(module.behavior.state_bits())
+            })
+            .collect();
+        let memory: usize = self.nands.iter().map(|id| modules[*id].inputs.len()).sum();
+        let mut state = BitSet::new(self.modules.len() + memory + other_bits.len() + 1);
+        for (n, module_id) in self.modules.iter().enumerate() {
+            state.set(n, modules[*module_id].state);
+        }
+
+        let mut n = self.modules.len();
+        for module_id in &self.nands {
+            let mut inputs: Vec<(&usize, &bool)> = modules[*module_id].inputs.iter().collect();
+            inputs.sort();
+            for (_, pulse) in inputs {
+                state.set(n, *pulse);
+                n += 1;
             }
         }
-        
+
+        for bit in other_bits {
+            state.set(n, bit);
+            n += 1;
+        }
+        state.set(n, modules[self.watched].state);
+
         state
     }
 
-    fn watch(&mut self, modules: &Vec<Module>) {
-        if modules[self.watched].state == self.active_state {
-            if self.steps != self.last_active {
-                // println!("t={}, Seen {} active.", self.steps, modules[self.watched].name);
-                self.active_times.push(self.steps);
-                self.last_active = self.steps;
-            }
+    // Called as each pulse is delivered.  The watched module's pulses reach
+    // all its outputs at the same moment, so any of them marks the change.
+    fn watch(&mut self, pulse: &Pulse) {
+        if pulse.from != self.watched {
+            return;
+        }
+
+        let at = (pulse.depth, pulse.root);
+        if pulse.high == self.active_state {
+            self.active_since.get_or_insert(at);
+        }
+        else if let Some(since) = self.active_since.take() {
+            self.press_spans.push((since, at));
         }
     }
 
+    // Called after each press.
     fn update(&mut self, modules: &Vec<Module>) {
         self.steps += 1;
 
-        let state = self.construct_state(modules);
-        if let std::collections::hash_map::Entry::Vacant(e) = self.observations.entry(state) {
-            e.insert(self.steps);
-        } else if self.period == 0 {
-            // set period
-            self.period = self.steps - self.observations[&state];
+        // Activity carries on into the next press.
+        if let Some(since) = self.active_since {
+            self.press_spans.push((since, PRESS_END));
+            self.active_since = Some(PRESS_START);
+        }
+        if !self.press_spans.is_empty() {
+            self.active_times.push((self.steps, std::mem::take(&mut self.press_spans)));
         }
 
- 
+        let state = self.construct_state(modules);
+        self.detector.observe(state);
     }
 
     fn found_period(&self) -> bool {
-        self.period != 0
+        self.detector.cycle().is_some()
     }
 
+    #[cfg(test)]
     fn get_period(&self) -> usize {
-        self.period
+        match self.detector.cycle() {
+            Some(cycle) => cycle.period,
+            None => 0,
+        }
+    }
+
+    // The presses when the watched module is active, once the period is
+    // known.  What happens in press n depends only on the state after press
+    // n-1, so the activity repeats from the press after the cycle starts.
+    // By the time the cycle is found, every press up to the end of its
+    // first period has been seen.
+    fn activity(&self) -> Option<Activity> {
+        let cycle = self.detector.cycle()?;
+        let start = cycle.prefix + 1;
+
+        Some(Activity {
+            early: self.active_times.iter().filter(|(press, _)| *press < start).cloned().collect(),
+            start,
+            period: cycle.period,
+            offsets: self.active_times.iter()
+                .filter(|(press, _)| (start..start + cycle.period).contains(press))
+                .map(|(press, spans)| (press - start, spans.clone()))
+                .collect(),
+        })
     }
+}
+
+// Merges n = r1 (mod m1) and n = r2 (mod m2) into n = r (mod lcm(m1, m2)),
+// or None if no n satisfies both.
+fn crt((r1, m1): (u128, u128), (r2, m2): (u128, u128)) -> Option<(u128, u128)> {
+    let ExtendedGcd { gcd, x, .. } = (m1 as i128).extended_gcd(&(m2 as i128));
+    let diff = r2 as i128 - r1 as i128;
+    if diff % gcd != 0 {
+        return None;
+    }
+
+    // n = r1 + m1 * k, where m1 * k = diff (mod m2)
+    let lcm = m1 / gcd as u128 * m2;
+    let step = (m2 as i128) / gcd;
+    let k = ((diff / gcd) % step * x).rem_euclid(step);
+    let r = (r1 as i128 + m1 as i128 * k).rem_euclid(lcm as i128);
 
-    // TODO
+    Some((r as u128, lcm))
+}
+
+// Whether the spans where every activity is active hold a delivery.  One
+// starting with the press doesn't: they were all active at the end of the
+// press before, so that press was the first.
+fn all_active(spans: &[Span]) -> bool {
+    spans.iter().any(|(from, _)| *from != PRESS_START)
+}
+
+// The first press with a moment when every activity is active, if any.
+fn first_common_press(activities: &[Activity]) -> Option<usize> {
+    // Presses before all the activities repeat are checked one at a time.
+    let start = activities.iter().map(|a| a.start).max()?;
+    let whole_press: Vec<Span> = vec![(PRESS_START, PRESS_END)];
+    let common = |press: usize| {
+        activities.iter().fold(whole_press.clone(), |spans, a| overlap(&spans, a.spans(press)))
+    };
+    if let Some(press) = (1..start).find(|press| all_active(&common(*press))) {
+        return Some(press);
+    }
+
+    // After that, each choice of offsets gives one residue class, along with
+    // the spans when all the activities so far are active.
+    let mut classes: Vec<(u128, u128, Vec<Span>)> = vec![(0, 1, whole_press)];
+    for a in activities {
+        let mut merged: Vec<(u128, u128, Vec<Span>)> = Vec::new();
+        for (r, m, spans) in &classes {
+            for (offset, offset_spans) in &a.offsets {
+                let spans = overlap(spans, offset_spans);
+                if spans.is_empty() {
+                    continue;
+                }
+
+                let residue = ((a.start + offset) % a.period) as u128;
+                if let Some((r, m)) = crt((*r, *m), (residue, a.period as u128)) {
+                    merged.push((r, m, spans));
+                }
+            }
+        }
+        classes = merged;
+    }
+
+    // Smallest press >= start in each class
+    classes.iter()
+        .filter(|(_, _, spans)| all_active(spans))
+        .map(|(r, m, _)| {
+            let start = start as u128;
+            if *r >= start { *r } else { r + (start - r).div_ceil(*m) * m }
+        })
+        .min()
+        .map(|press| press as usize)
+}
+
+// A pulse delivered during a simulation, as recorded in the trace.  depth
+// counts the pulses that led to it from the button, and root is the
+// broadcaster output it descends from (0 for the button's own pulse).  The
+// event queue is first in, first out, so a press delivers its pulses in
+// order of depth, and pulses of the same depth in order of root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pulse {
+    press: usize,
+    from: usize,
+    to: usize,
+    high: bool,
+    depth: usize,
+    root: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SimError {
+    // A module named in a debugging request isn't in the network.
+    UnknownModule(String),
+    // The named module's prefix isn't a registered kind.
+    UnknownKind(String),
+    // rx isn't fed through one conjunction by independent sub-networks.
+    NotIndependent,
+    // The sub-networks feeding rx didn't all repeat within this many presses.
+    NoRepeat { budget: usize },
+}
+
+impl Display for SimError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            SimError::UnknownModule(name) => write!(f, "no module named {name}"),
+            SimError::UnknownKind(name) => write!(f, "module {name} has an unknown prefix"),
+            SimError::NotIndependent => write!(f, "rx isn't fed by independent sub-networks"),
+            SimError::NoRepeat { budget } => write!(f, "rx's sub-networks don't repeat within {budget} presses"),
+        }
+    }
 }
 
 struct Sim {
     module_name_to_id: HashMap<String, usize>,
     modules: Vec<Module>,     // states of nodes
-    events: VecDeque<(usize, usize, bool)>,
+    events: VecDeque<Pulse>,
     state_sets: Vec<StateSet>,
 
     low_pulses: usize,
     high_pulses: usize,
+    // kind of module -> (low, high) pulses received
+    kind_pulses: HashMap<&'static str, (usize, usize)>,
+    // button presses so far
     t: usize,
+
+    // Debugging: pulses delivered (if tracing), the modules to trace (None
+    // for all of them), and (module, polarity) pairs to stop at.
+    trace: Option<Vec<Pulse>>,
+    trace_filter: Option<Vec<usize>>,
+    breakpoints: Vec<(usize, bool)>,
 }
 
 impl Sim {
-    fn new(input: &Input) -> Sim {
+    fn new(input: &Input) -> Result<Sim, SimError> {
+        Sim::with_kinds(input, &Kinds::standard())
+    }
+
+    fn with_kinds(input: &Input, kinds: &Kinds) -> Result<Sim, SimError> {
         let mut id = 0;
         let mut module_name_to_id: HashMap<String, usize> = HashMap::new();
         let mut modules: Vec<Module> = Vec::new();
 
         // Create button module
-        let button = Module::new(ModType::Node, "button", id);
+        let button = Module::new(ModType::Node, id, Box::new(Broadcast));
         modules.push(button);
         module_name_to_id.insert("button".to_string(), id);
         // println!("registered button");
@@ -232,7 +668,8 @@ impl Sim {
         // Create Modules from input
         for line in &input.lines {
             let name = &line.1;
-            let module = Module::new(line.0, &name, id);
+            let behavior = kinds.make(line.0).ok_or_else(|| SimError::UnknownKind(line.1.clone()))?;
+            let module = Module::new(line.0, id, behavior);
             modules.push(module);
             module_name_to_id.insert(name.to_string(), id);
             // println!("registered name {}", name);
@@ -248,7 +685,7 @@ impl Sim {
                     },
                     None => {
                         // output neeeds a node created.
-                        let module = Module::new(ModType::Node, out_name, id);
+                        let module = Module::new(ModType::Node, id, Box::new(Broadcast));
 
                         modules.push(module);
                         module_name_to_id.insert(out_name.to_string(), id);
@@ -272,8 +709,7 @@ impl Sim {
             }
         }
 
-        // Event is (from_id, to_id, state)
-        let events: VecDeque<(usize, usize, bool)> = VecDeque::new(); // event queue
+        let events: VecDeque<Pulse> = VecDeque::new(); // event queue
 
         let state_sets = Vec::new();
 
@@ -285,34 +721,80 @@ impl Sim {
             state_sets, 
             low_pulses: 0, 
             high_pulses: 0,
+            kind_pulses: HashMap::new(),
             t: 0,
+            trace: None,
+            trace_filter: None,
+            breakpoints: Vec::new(),
         };
 
         sim.setup_state_sets();
 
-        sim
+        Ok(sim)
     }
 
     fn setup_state_sets(&mut self) {
-        match self.module_name_to_id.get("rx") {
-            Some(rx_id) => {
-                let rx = &self.modules[*rx_id];
-                // create the state sets for each of 'rx' inputs
-                for (input_id, _) in rx.inputs.iter() {
-                    let input = &self.modules[*input_id];
-
-                    // create state sets for each second-level input to rx.  (expecting 4)
-                    for (input_id2, _) in input.inputs.iter() {
-                        // let input2 = self.modules[*input_id2];
-                        
-                        // println!("Create state set for {}", input_name2);
-                        let state_set2 = StateSet::create(*input_id2, true, &self.modules);  // borked
-                        self.state_sets.push(state_set2);
-                    }
+        if let Some((_, feeders)) = self.decompose("rx") {
+            for feeder in feeders {
+                let state_set = StateSet::create(feeder, true, &self.modules);
+                self.state_sets.push(state_set);
+            }
+        }
+    }
+
+    // Modules whose pulses can reach this one, not counting the broadcaster
+    // or the button, which every module depends on.
+    fn upstream(&self, id: usize) -> BitSet {
+        let mut seen = BitSet::new(self.modules.len());
+        let mut to_visit: Vec<usize> = vec![id];
+        while let Some(visiting) = to_visit.pop() {
+            for input_id in self.modules[visiting].inputs.keys() {
+                if self.modules[*input_id].mod_type != ModType::Node && seen.insert(*input_id) {
+                    to_visit.push(*input_id);
                 }
             }
-            None => { } // do nothing, return state_sets will be empty vector. 
         }
+
+        seen
+    }
+
+    // If the target's only input is a conjunction, and that conjunction's
+    // inputs are driven by separate groups of modules, returns
+    // (conjunction, its inputs).  The target gets a low pulse when all the
+    // inputs have most recently sent high pulses, and each group runs
+    // independently of the others.
+    fn decompose(&self, target: &str) -> Option<(usize, Vec<usize>)> {
+        let target = &self.modules[*self.module_name_to_id.get(target)?];
+        if target.inputs.len() != 1 {
+            return None;
+        }
+
+        let hub_id = *target.inputs.keys().next().unwrap();
+        let hub = &self.modules[hub_id];
+        if hub.mod_type != ModType::Nand {
+            return None;
+        }
+
+        let mut feeders: Vec<usize> = hub.inputs.keys().copied().collect();
+        feeders.sort();
+
+        let mut claimed = BitSet::new(self.modules.len());
+        for feeder in &feeders {
+            let mut group = self.upstream(*feeder);
+            group.insert(*feeder);
+            if group.contains(hub_id) {
+                return None;
+            }
+
+            let mut shared = group.clone();
+            shared.intersect_with(&claimed);
+            if !shared.is_empty() {
+                return None;
+            }
+            claimed.union_with(&group);
+        }
+
+        Some((hub_id, feeders))
     }
 
     fn product(&self) -> usize {
@@ -329,14 +811,28 @@ impl Sim {
         // push one event, a low signal to 'broadcaster'
         let button_id = self.module_name_to_id.get("button").unwrap();
         let broadcaster_id = self.module_name_to_id.get("broadcaster").unwrap();
-        self.events.push_back((*button_id, *broadcaster_id, false));
+        self.events.push_back(Pulse { press: self.t, from: *button_id, to: *broadcaster_id, high: false, depth: 0, root: 0 });
     }
 
-    fn sim(&mut self) {
+    // Deliver pulses until the event queue is empty, or until one hits a
+    // breakpoint.  In that case, returns the pulse that hit it.  Calling sim
+    // again carries on from there.
+    fn sim(&mut self) -> Option<Pulse> {
          // run until the event queue is empty
         while !self.events.is_empty() {
             // pop an event
-            let (from_id, to_id, in_pulse) = self.events.pop_front().unwrap();
+            let pulse = self.events.pop_front().unwrap();
+            let Pulse { from: from_id, to: to_id, high: in_pulse, .. } = pulse;
+
+            if let Some(trace) = &mut self.trace {
+                let traced = match &self.trace_filter {
+                    Some(filter) => filter.contains(&from_id) || filter.contains(&to_id),
+                    None => true,
+                };
+                if traced {
+                    trace.push(pulse);
+                }
+            }
 
             // count it
             if in_pulse {
@@ -348,77 +844,54 @@ impl Sim {
 
             // Locate the node
             let node = &mut self.modules[to_id];
-/*
-            if node.name == "zf" && self.t == 3947 {
-                if in_pulse {
-                    println!("t={}, zf received high", self.t);
-                }
-                else {
-                    println!("t={}, zf received low", self.t);
-                }
-            }
-*/
-
-            // Update the node and propagate the pulses
-            let out_pulse = match node.mod_type {
-                ModType::Node => {
-                    // Propagate pulses to all outputs
-                    node.state = in_pulse;
-                    Some(node.state)
-                }
-                ModType::Nand => {
-
-                    // Update this input
-                    node.inputs.insert(from_id, in_pulse);
-
-                    // Evaluate all inputs, set state false if all inputs true
-                    let mut all_true = true;
-                    for (_other_name, other_state) in &node.inputs {
-                        if *other_state == false {
-                            all_true = false;
-                            break;
-                        }
-                    }
 
-                    node.state = !all_true;
-                    Some(node.state)
-                }
+            // Update the node and propagate the pulses.  Its state is the
+            // last pulse it sent.
+            node.inputs.insert(from_id, in_pulse);
+            let counts = self.kind_pulses.entry(node.behavior.kind()).or_insert((0, 0));
+            if in_pulse {
+                counts.1 += 1;
+            }
+            else {
+                counts.0 += 1;
+            }
 
-                ModType::FlipFlop => {
-                    if in_pulse {
-                        // With a high pulse input, a flipflop does nothing
-                        None
-                    }
-                    else {
-                        // With a low pulse, switch state, send corresponding pulse
-                        node.state = !node.state;
-                        Some(node.state)
-                    }
-                }
-            };
+            let out_pulse = node.behavior.receive(from_id, in_pulse, &node.inputs);
+            if let Some(out_pulse) = out_pulse {
+                node.state = out_pulse;
+            }
 
             // Propagate a pulse to each output
             if let Some(out_pulse) = out_pulse {
-                for other_id in &node.outputs {
-                    self.events.push_back((node.id, *other_id, out_pulse));
+                for (n, other_id) in node.outputs.iter().enumerate() {
+                    let root = if pulse.depth == 0 { n } else { pulse.root };
+                    self.events.push_back(Pulse {
+                        press: self.t, from: node.id, to: *other_id, high: out_pulse, depth: pulse.depth + 1, root
+                    });
                 }
             }
 
             // Let state sets observe their watched nodes
             for ss in self.state_sets.iter_mut() {
-                ss.watch(&self.modules);
+                ss.watch(&pulse);
+            }
+
+            if self.breakpoints.contains(&(to_id, in_pulse)) {
+                return Some(pulse);
             }
         }
+
+        None
     }
 
     fn run(&mut self, buttons: usize) {
         for _ in 0..buttons {
             self.button();
             self.sim();
-            self.t += 1;
         }
     }
 
+    #[cfg(test)]
     fn run_to_known_periods(&mut self) -> usize {
         let mut found_periods = false;
         while !found_periods {
@@ -441,42 +914,164 @@ impl Sim {
         overall_period
     }
 
-    /*
-    // create a vector of state sets.  A StateSet represents a group of flipflop modules that
-    // communicate with each other.
-    fn get_state_sets(&self) -> Vec<StateSet> {
+    // The first button press that sends a low pulse to rx, when rx is fed
+    // through a conjunction by independent sub-networks.  Each is simulated
+    // until it repeats, for at most budget presses, and the first press with
+    // a moment when they all send the conjunction high is found from their
+    // periods.  Ok(None) if there's no such press.
+    fn rx_by_periods(input: &Input, budget: usize) -> Result<Option<usize>, SimError> {
+        let mut sim = Sim::new(input)?;
+        if sim.state_sets.is_empty() {
+            return Err(SimError::NotIndependent);
+        }
 
-        let mut sets = Vec::new();
+        let activities = sim.activities(budget).ok_or(SimError::NoRepeat { budget })?;
+        Ok(first_common_press(&activities))
+    }
 
-        // create the state sets for each of 'rx' inputs
-        let rx_id = self.module_name_to_id.get("rx").unwrap();
-        let rx = &self.modules[*rx_id];
-        for (input_id, _) in rx.inputs.iter() {
-            let input = &self.modules[*input_id];
+    // Presses the button until every state set repeats, then reports when
+    // each is active.  None if that takes more than budget presses.
+    fn activities(&mut self, budget: usize) -> Option<Vec<Activity>> {
+        while !self.state_sets.iter().all(|ss| ss.found_period()) {
+            if self.t >= budget {
+                return None;
+            }
 
-            // create state sets for each second-level input to rx.
-            for (input_id2, _) in input.inputs.iter() {
-                
-                let state_set2 = StateSet::create(*input_id2, true, &self.modules);
-                sets.push(state_set2);
+            self.button();
+            self.sim();
+            for ss in self.state_sets.iter_mut() {
+                ss.update(&self.modules);
             }
         }
 
-        sets
+        self.state_sets.iter().map(|ss| ss.activity()).collect()
+    }
+
+    // Plain simulation, up to budget presses.  Ok(None) if rx doesn't get a
+    // low pulse in that time, or there's no rx.
+    fn simulate_to_rx(input: &Input, budget: usize) -> Result<Option<usize>, SimError> {
+        let mut sim = Sim::new(input)?;
+        if sim.add_breakpoint("rx", false).is_err() {
+            return Ok(None);
+        }
+
+        Ok(sim.run_to_break(budget).map(|pulse| pulse.press))
+    }
+}
+
+// Debugging tools: tracing, breakpoints and drawing the network.
+impl Sim {
+    fn module_id(&self, name: &str) -> Result<usize, SimError> {
+        self.module_name_to_id.get(name).copied().ok_or_else(|| SimError::UnknownModule(name.to_string()))
     }
-    */
 
-    /*
-    fn run_to_rx(&mut self) -> usize {
-        let state_sets = self.get_state_sets());
+    // Finish any press a breakpoint stopped partway through, then press the
+    // button until a breakpoint is hit, at most max_presses more times.
+    fn run_to_break(&mut self, max_presses: usize) -> Option<Pulse> {
+        if let Some(pulse) = self.sim() {
+            return Some(pulse);
+        }
+
+        for _ in 0..max_presses {
+            self.button();
+            if let Some(pulse) = self.sim() {
+                return Some(pulse);
+            }
+        }
+
+        None
+    }
 
-        // TODO
-        0
+    // Start recording the pulses to or from the named modules, or all pulses
+    // if modules is None.  Clears any earlier trace.
+    #[cfg(test)]
+    fn start_trace(&mut self, modules: Option<&[&str]>) -> Result<(), SimError> {
+        self.trace_filter = match modules {
+            Some(names) => Some(names.iter().map(|name| self.module_id(name)).collect::<Result<_, _>>()?),
+            None => None,
+        };
+        self.trace = Some(Vec::new());
 
+        Ok(())
+    }
+
+    // The recorded pulses, or an empty list when not tracing.
+    #[cfg(test)]
+    fn trace(&self) -> &[Pulse] {
+        match &self.trace {
+            Some(trace) => trace,
+            None => &[],
+        }
+    }
+
+    // The recorded pulses from one button press
+    #[cfg(test)]
+    fn press_trace(&self, press: usize) -> Vec<Pulse> {
+        self.trace().iter().filter(|pulse| pulse.press == press).copied().collect()
+    }
+
+    #[cfg(test)]
+    fn name(&self, id: usize) -> &str {
+        self.module_name_to_id.iter()
+            .find(|(_, module_id)| **module_id == id)
+            .map(|(name, _)| name.as_str())
+            .unwrap()
+    }
+
+    // A pulse in the puzzle's notation: "broadcaster -low-> a"
+    #[cfg(test)]
+    fn describe(&self, pulse: &Pulse) -> String {
+        format!("{} -{}-> {}",
+            self.name(pulse.from),
+            if pulse.high { "high" } else { "low" },
+            self.name(pulse.to))
+    }
+
+    // Stop the simulation when the module receives a pulse with this polarity
+    fn add_breakpoint(&mut self, name: &str, high: bool) -> Result<(), SimError> {
+        self.breakpoints.push((self.module_id(name)?, high));
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    // The module network in Graphviz format.  Flip-flops are drawn as
+    // diamonds, conjunctions as inverted triangles and other kinds as
+    // ellipses, labelled with their prefixes as in the input.
+    #[cfg(test)]
+    fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph modules {\n");
+
+        for module in &self.modules {
+            let (shape, prefix) = match module.mod_type {
+                ModType::Node => ("box", String::new()),
+                ModType::FlipFlop => ("diamond", "%".to_string()),
+                ModType::Nand => ("invtriangle", "&".to_string()),
+                ModType::Custom { prefix, arg: Some(arg) } => ("ellipse", format!("{prefix}{arg}")),
+                ModType::Custom { prefix, arg: None } => ("ellipse", prefix.to_string()),
+            };
+            let name = self.name(module.id);
+            dot += &format!("    {name} [shape={shape}, label=\"{prefix}{name}\"];\n");
+        }
+
+        for module in &self.modules {
+            for out_id in &module.outputs {
+                dot += &format!("    {} -> {};\n", self.name(module.id), self.name(*out_id));
+            }
+        }
+
+        dot += "}\n";
+        dot
     }
-    */
 }
 
+// Most presses to simulate in part 2
+const PRESS_BUDGET: usize = 1_000_000;
+
 pub struct Day20<'a> {
     input_filename: &'a str,
 }
@@ -491,7 +1086,7 @@ impl<'a> Day for Day20<'a> {
     fn part1(&self) -> Answer {
         let input = Input::read(self.input_filename);
 
-        let mut sim = Sim::new(&input);
+        let mut sim = Sim::new(&input).unwrap();
 
         sim.run(1000);
 
@@ -503,25 +1098,52 @@ impl<'a> Day for Day20<'a> {
 
         let input = Input::read(self.input_filename);
 
-        let mut sim = Sim::new(&input);
-
-
-        // run until all state sets have detected periodic behavior
-        let long_period = sim.run_to_known_periods();
-
-        // I've seen that each component of the period only activates ones per sub-cycle and
-        // it activates on the last time step of the cycle.
-        // So the product of all the sub-cycle periods is the point where the final signal
-        // is activated.
+        let presses = match Sim::rx_by_periods(&input, PRESS_BUDGET) {
+            Err(SimError::NotIndependent | SimError::NoRepeat { .. }) => {
+                // Only practical when rx goes low within the budget.
+                Sim::simulate_to_rx(&input, PRESS_BUDGET)
+            }
+            found => found,
+        };
 
-        
-        Answer::Numeric(long_period)   // TODO-DW
+        match presses {
+            Ok(Some(presses)) => Answer::Numeric(presses),
+            Ok(None) => Answer::String("rx never gets a low pulse".to_string()),
+            Err(e) => Answer::String(e.to_string()),
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{day::{Answer, Day}, day20::{Day20, Input, Sim, StateSet}};
+    use std::collections::HashMap;
+
+    use crate::{day::{Answer, Day}, day20::{Day20, Input, Sim, SimError, StateSet, Activity, crt, first_common_press}};
+    use crate::day20::{Span, PRESS_START, PRESS_END};
+    use crate::day20::{Behavior, Gate, Kinds, ModType};
+
+    fn network(lines: &[&str]) -> Input {
+        Input { lines: lines.iter().map(|line| Input::process_line(line).unwrap()).collect() }
+    }
+
+    // Two binary counters, on 2 and 3 flipflops.  Each has a conjunction
+    // that goes low when its counter shows `all on`, and an inverter
+    // after that feeds the hub in front of rx.
+    fn counters() -> Input {
+        network(&[
+            "broadcaster -> aa, ba",
+            "%aa -> ab, fa",
+            "%ab -> fa",
+            "&fa -> ia",
+            "&ia -> hub",
+            "%ba -> bb, fb",
+            "%bb -> bc, fb",
+            "%bc -> fb",
+            "&fb -> ib",
+            "&ib -> hub",
+            "&hub -> rx",
+        ])
+    }
 
     #[test]
     fn test_input1() {
@@ -538,7 +1160,7 @@ mod test {
     #[test]
     fn test_button1() {
         let input = Input::read("examples/day20_example1.txt");
-        let mut sim = Sim::new(&input);
+        let mut sim = Sim::new(&input).unwrap();
 
         sim.run(1);
         assert_eq!(sim.low_pulses, 8);
@@ -549,7 +1171,7 @@ mod test {
     #[test]
     fn test_sim1() {
         let input = Input::read("examples/day20_example1.txt");
-        let mut sim = Sim::new(&input);
+        let mut sim = Sim::new(&input).unwrap();
 
         sim.run(1000);
         assert_eq!(sim.low_pulses, 8000);
@@ -566,7 +1188,7 @@ mod test {
     #[test]
     fn test_sim2() {
         let input = Input::read("examples/day20_example2.txt");
-        let mut sim = Sim::new(&input);
+        let mut sim = Sim::new(&input).unwrap();
 
         sim.run(1000);
         assert_eq!(sim.high_pulses, 2750);
@@ -575,17 +1197,364 @@ mod test {
         assert_eq!(sim.product(), 4250*2750);
     }
 
+    #[test]
+    fn test_trace() {
+        let input = Input::read("examples/day20_example1.txt");
+        let mut sim = Sim::new(&input).unwrap();
+
+        sim.start_trace(None).unwrap();
+        sim.run(2);
+
+        let first: Vec<String> = sim.press_trace(1).iter().map(|p| sim.describe(p)).collect();
+        assert_eq!(first, vec![
+            "button -low-> broadcaster",
+            "broadcaster -low-> a",
+            "broadcaster -low-> b",
+            "broadcaster -low-> c",
+            "a -high-> b",
+            "b -high-> c",
+            "c -high-> inv",
+            "inv -low-> a",
+            "a -low-> b",
+            "b -low-> c",
+            "c -low-> inv",
+            "inv -high-> a",
+        ]);
+        assert_eq!(sim.trace().len(), 24);
+        assert_eq!(sim.press_trace(2).len(), 12);
+
+        sim.start_trace(Some(&["inv"])).unwrap();
+        sim.run(1);
+        let traced: Vec<String> = sim.trace().iter().map(|p| sim.describe(p)).collect();
+        assert_eq!(traced, vec!["c -high-> inv", "inv -low-> a", "c -low-> inv", "inv -high-> a"]);
+        assert!(sim.trace().iter().all(|p| p.press == 3));
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let input = Input::read("examples/day20_example2.txt");
+        let mut sim = Sim::new(&input).unwrap();
+
+        // output receives a low pulse once a and b are both on.
+        sim.add_breakpoint("output", false).unwrap();
+        let hit = sim.run_to_break(10).unwrap();
+        assert_eq!(hit.press, 1);
+        assert_eq!(sim.describe(&hit), "con -low-> output");
+
+        // Resuming finishes the press with the same counts as not stopping.
+        sim.clear_breakpoints();
+        assert_eq!(sim.sim(), None);
+        let mut plain = Sim::new(&input).unwrap();
+        plain.run(1);
+        assert_eq!((sim.low_pulses, sim.high_pulses), (plain.low_pulses, plain.high_pulses));
+
+        sim.add_breakpoint("broadcaster", true).unwrap();
+        assert_eq!(sim.run_to_break(10), None);
+        assert_eq!(sim.t, 11);
+
+        // Resuming finishes the stopped press before pressing again.
+        let mut sim = Sim::new(&input).unwrap();
+        sim.add_breakpoint("output", false).unwrap();
+        sim.run_to_break(10).unwrap();
+        assert_eq!(sim.run_to_break(0), None);
+        assert_eq!(sim.t, 1);
+        assert_eq!(sim.run_to_break(2).map(|pulse| pulse.press), Some(3));
+
+        // Unknown modules are reported rather than panicking.
+        let unknown = SimError::UnknownModule("nowhere".to_string());
+        assert_eq!(sim.add_breakpoint("nowhere", true), Err(unknown.clone()));
+        assert_eq!(sim.start_trace(Some(&["a", "nowhere"])), Err(unknown.clone()));
+        assert_eq!(unknown.to_string(), "no module named nowhere");
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let input = Input::read("examples/day20_example2.txt");
+        let sim = Sim::new(&input).unwrap();
+        let dot = sim.to_dot();
+
+        assert!(dot.starts_with("digraph modules {\n"));
+        assert!(dot.contains("    a [shape=diamond, label=\"%a\"];\n"));
+        assert!(dot.contains("    con [shape=invtriangle, label=\"&con\"];\n"));
+        assert!(dot.contains("    output [shape=box, label=\"output\"];\n"));
+        assert!(dot.contains("    a -> con;\n"));
+        assert_eq!(dot.matches(" -> ").count(), 6);
+    }
+
     #[test]
     fn test_part1_ex2() {
         let d = Day20::new("examples/day20_example2.txt");
         assert_eq!(d.part1(), Answer::Numeric(4250*2750));
     }
 
+    #[test]
+    fn test_large_network() {
+        // 150 flipflops that toggle every press, and a 3 bit ripple counter,
+        // all feeding one output.
+        let mut lines: Vec<String> = Vec::new();
+        let letter = |n: usize| char::from(b'a' + n as u8);
+        let toggles: Vec<String> = (0..150).map(|n| format!("t{}{}", letter(n / 26), letter(n % 26))).collect();
+        lines.push(format!("broadcaster -> {}, ca", toggles.join(", ")));
+        for name in &toggles {
+            lines.push(format!("%{name} -> sink"));
+        }
+        lines.push("%ca -> cb".to_string());
+        lines.push("%cb -> cc".to_string());
+        lines.push("%cc -> sink".to_string());
+        let input = Input { lines: lines.iter().map(|line| Input::process_line(line).unwrap()).collect() };
+
+        let mut sim = Sim::new(&input).unwrap();
+        assert!(sim.modules.len() > 128);
+
+        let sink = sim.module_name_to_id["sink"];
+        let state_set = StateSet::create(sink, true, &sim.modules);
+        assert_eq!(state_set.modules.len(), 153);
+        sim.state_sets.push(state_set);
+
+        assert_eq!(sim.run_to_known_periods(), 8);
+        assert_eq!(sim.state_sets[0].detector.cycle().unwrap().prefix, 0);
+    }
+
+    #[test]
+    fn test_kind_pulses() {
+        let input = Input::read("examples/day20_example1.txt");
+        let mut sim = Sim::new(&input).unwrap();
+
+        sim.run(1);
+        assert_eq!(sim.kind_pulses["node"], (1, 0));
+        assert_eq!(sim.kind_pulses["flip-flop"], (6, 3));
+        assert_eq!(sim.kind_pulses["conjunction"], (1, 1));
+    }
+
+    #[test]
+    fn test_gates() {
+        let inputs = |pulses: &[bool]| -> HashMap<usize, bool> {
+            pulses.iter().copied().enumerate().collect()
+        };
+
+        for (pulses, nand, and, or, xor) in [
+            (vec![false, false], true, false, false, false),
+            (vec![true, false], true, false, true, true),
+            (vec![true, true], false, true, true, false),
+            (vec![true, true, true], false, true, true, true),
+        ] {
+            let inputs = inputs(&pulses);
+            let eval = |mut gate: Gate| gate.receive(0, true, &inputs);
+            assert_eq!(eval(Gate::NAND), Some(nand));
+            assert_eq!(eval(Gate::AND), Some(and));
+            assert_eq!(eval(Gate::OR), Some(or));
+            assert_eq!(eval(Gate::XOR), Some(xor));
+        }
+    }
+
+    #[test]
+    fn test_counter_and_delay() {
+        assert_eq!(Input::process_line("#3div -> out").unwrap().0, ModType::Custom { prefix: '#', arg: Some(3) });
+        assert_eq!(Input::process_line("~dl -> out").unwrap().0, ModType::Custom { prefix: '~', arg: None });
+
+        let input = network(&[
+            "broadcaster -> div, ff",
+            "#3div -> a",
+            "%ff -> dl",
+            "~1dl -> b",
+        ]);
+        let mut sim = Sim::new(&input).unwrap();
+        sim.start_trace(Some(&["a", "b"])).unwrap();
+        sim.run(6);
+
+        let traced: Vec<(usize, String)> = sim.trace().iter().map(|p| (p.press, sim.describe(p))).collect();
+        assert_eq!(traced, vec![
+            (1, "dl -low-> b".to_string()),
+            (2, "dl -high-> b".to_string()),
+            (3, "div -low-> a".to_string()),
+            (3, "dl -low-> b".to_string()),
+            (4, "dl -high-> b".to_string()),
+            (5, "dl -low-> b".to_string()),
+            (6, "div -low-> a".to_string()),
+            (6, "dl -high-> b".to_string()),
+        ]);
+        assert_eq!(sim.kind_pulses["counter"], (6, 0));
+        assert_eq!(sim.kind_pulses["delay"], (3, 3));
+    }
+
+    #[test]
+    fn test_bad_lines() {
+        // Lines must match in full, and a number needs a prefix before it.
+        for line in ["3abc -> x", "%%a -> b", "%a -> b c", "a -> ", "a -> b,c", "ab -> c!"] {
+            assert_eq!(Input::process_line(line), None, "{line}");
+        }
+
+        let input = network(&["broadcaster -> x", "@x -> y"]);
+        let err = Sim::new(&input).err().unwrap();
+        assert_eq!(err, SimError::UnknownKind("x".to_string()));
+        assert_eq!(err.to_string(), "module x has an unknown prefix");
+        assert_eq!(Sim::rx_by_periods(&input, 10), Err(err));
+    }
+
+    // Sends the opposite of each pulse it receives.
+    struct Invert;
+
+    impl Behavior for Invert {
+        fn kind(&self) -> &'static str {
+            "not"
+        }
+
+        fn receive(&mut self, _from: usize, high: bool, _inputs: &HashMap<usize, bool>) -> Option<bool> {
+            Some(!high)
+        }
+    }
+
+    #[test]
+    fn test_register_kind() {
+        let input = network(&["broadcaster -> no", "!no -> out"]);
+        let mut kinds = Kinds::new();
+        kinds.register('!', |_| Box::new(Invert));
+
+        let mut sim = Sim::with_kinds(&input, &kinds).unwrap();
+        sim.start_trace(Some(&["out"])).unwrap();
+        sim.run(1);
+
+        assert_eq!(sim.describe(&sim.trace()[0]), "no -high-> out");
+        assert_eq!(sim.kind_pulses["not"], (1, 0));
+        assert!(sim.to_dot().contains("    no [shape=ellipse, label=\"!no\"];\n"));
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt((2, 3), (3, 5)), Some((8, 15)));
+        assert_eq!(crt((1, 4), (3, 6)), Some((9, 12)));
+        assert_eq!(crt((0, 4), (1, 6)), None);
+        assert_eq!(crt((0, 1), (4, 7)), Some((4, 7)));
+    }
+
+    #[test]
+    fn test_first_common_press() {
+        // Active from a delivery at depth 2 to the end of the press
+        let late = |root: usize| -> Vec<Span> { vec![((2, root), PRESS_END)] };
+
+        let a = Activity { early: vec![(1, late(0))], start: 3, period: 4, offsets: vec![(2, late(0))] };
+        let b = Activity { early: vec![], start: 1, period: 6, offsets: vec![(3, late(1))] };
+
+        // a: 1, 5, 9, 13, ...  b: 4, 10, 16, 22, ...
+        assert!(a.is_active(1) && a.is_active(9) && !a.is_active(3));
+        assert_eq!(first_common_press(&[a]), Some(1));
+
+        let a = Activity { early: vec![], start: 3, period: 4, offsets: vec![(2, late(0)), (3, late(0))] };
+        // a: 5, 6, 9, 10, ...
+        assert_eq!(first_common_press(&[a, b]), Some(10));
+
+        let never = Activity { early: vec![], start: 1, period: 2, offsets: vec![(0, late(0))] };
+        let odd = Activity { early: vec![], start: 1, period: 2, offsets: vec![(1, late(1))] };
+        assert_eq!(first_common_press(&[never, odd]), None);
+
+        // Active in the same presses, but never at the same moment
+        let early = Activity { early: vec![], start: 1, period: 1, offsets: vec![(0, vec![((2, 0), (4, 0))])] };
+        let later = Activity { early: vec![], start: 1, period: 1, offsets: vec![(0, vec![((5, 1), (6, 1))])] };
+        assert_eq!(first_common_press(&[early, later]), None);
+
+        let early = Activity { early: vec![], start: 1, period: 1, offsets: vec![(0, vec![((2, 0), (4, 0))])] };
+        let meets = Activity { early: vec![], start: 1, period: 1, offsets: vec![(0, vec![((3, 1), (6, 1))])] };
+        assert_eq!(first_common_press(&[early, meets]), Some(1));
+
+        // Both active all press, with no delivery while they are
+        let all = || Activity { early: vec![], start: 1, period: 1, offsets: vec![(0, vec![(PRESS_START, PRESS_END)])] };
+        assert_eq!(first_common_press(&[all(), all()]), None);
+    }
+
+    #[test]
+    fn test_decompose() {
+        let input = counters();
+        let sim = Sim::new(&input).unwrap();
+
+        let (hub, feeders) = sim.decompose("rx").unwrap();
+        assert_eq!(sim.name(hub), "hub");
+        let names: Vec<&str> = feeders.iter().map(|id| sim.name(*id)).collect();
+        assert_eq!(names, vec!["ia", "ib"]);
+        assert_eq!(sim.state_sets.len(), 2);
+
+        // A flipflop shared between the counters ties them together.
+        let input = network(&[
+            "broadcaster -> aa",
+            "%aa -> fa, fb",
+            "&fa -> hub",
+            "&fb -> hub",
+            "&hub -> rx",
+        ]);
+        let sim = Sim::new(&input).unwrap();
+        assert!(sim.decompose("rx").is_none());
+        assert!(sim.state_sets.is_empty());
+        assert!(sim.decompose("nothing").is_none());
+    }
+
+    #[test]
+    fn test_activities() {
+        let input = counters();
+        let mut sim = Sim::new(&input).unwrap();
+
+        let activities = sim.activities(100).unwrap();
+        assert_eq!(activities[0].period, 4);
+        assert_eq!(activities[1].period, 8);
+
+        // The inverters go high when their counters reach 3 and 7, and low
+        // again early in the next press, when the counters wrap round.
+        for press in 1..=40 {
+            assert_eq!(activities[0].is_active(press), press % 4 == 3 || press % 4 == 0, "press {press}");
+            assert_eq!(activities[1].is_active(press), press % 8 == 7 || press % 8 == 0, "press {press}");
+        }
+        let rises = activities[0].spans(3);
+        assert!(rises.len() == 1 && rises[0].0 != PRESS_START && rises[0].1 == PRESS_END);
+        let falls = activities[0].spans(4);
+        assert!(falls.len() == 1 && falls[0].0 == PRESS_START && falls[0].1 != PRESS_END);
+
+        assert!(Sim::new(&input).unwrap().activities(3).is_none());
+    }
+
+    #[test]
+    fn test_first_rx_press() {
+        let input = counters();
+
+        assert_eq!(Sim::rx_by_periods(&input, 100), Ok(Some(7)));
+        assert_eq!(Sim::simulate_to_rx(&input, 100).unwrap(), Some(7));
+        assert_eq!(Sim::rx_by_periods(&input, 5), Err(SimError::NoRepeat { budget: 5 }));
+
+        // Both sub-networks send hub a high pulse during press 1, but fa's
+        // has been cancelled by the time fb's arrives.  In press 2, fa goes
+        // high again while fb is still high.
+        let input = network(&[
+            "broadcaster -> xa, xb",
+            "%xa -> fa, za",
+            "&za -> ya",
+            "&ya -> fa",
+            "&fa -> hub",
+            "%xb -> ca",
+            "&ca -> cb",
+            "&cb -> cc",
+            "&cc -> fb",
+            "&fb -> hub",
+            "&hub -> rx",
+        ]);
+        assert_eq!(Sim::simulate_to_rx(&input, 100).unwrap(), Some(2));
+        assert_eq!(Sim::rx_by_periods(&input, 100), Ok(Some(2)));
+
+        // Periods don't apply when the network doesn't split up.
+        let input = network(&[
+            "broadcaster -> aa",
+            "%aa -> ab, fa, fb",
+            "%ab -> fb",
+            "&fa -> hub",
+            "&fb -> hub",
+            "&hub -> rx",
+        ]);
+        assert!(Sim::simulate_to_rx(&input, 100).unwrap().is_some());
+        assert_eq!(Sim::rx_by_periods(&input, 100), Err(SimError::NotIndependent));
+        assert_eq!(SimError::NotIndependent.to_string(), "rx isn't fed by independent sub-networks");
+    }
+
     #[test]
     fn test_state_set() {
         // TODO : read input, create the state set for 'rx'
         let input = Input::read("data_aoc2023/day20.txt");
-        let sim = Sim::new(&input);
+        let sim = Sim::new(&input).unwrap();
         // println!("modules: {:?}", sim.modules.keys());
         let rx = sim.module_name_to_id.get("rx").unwrap();
 
@@ -600,7 +1569,7 @@ mod test {
     fn test_state_sets2() {
         // read input, get rx module
         let input = Input::read("data_aoc2023/day20.txt");
-        let sim = Sim::new(&input);
+        let sim = Sim::new(&input).unwrap();
         // println!("modules: {:?}", sim.modules.keys());
         let rx_id = sim.module_name_to_id.get("rx").unwrap();
         let rx = &sim.modules[*rx_id];
@@ -626,44 +1595,21 @@ mod test {
 
     }
 
-    /*
-    #[test]
-    fn test_get_state_sets() {
-        let input = Input::read("data_aoc2023/day20.txt");
-        let sim = Sim::new(&input);
-
-        let state_sets = sim.get_state_sets();
-        assert_eq!(state_sets.len(), 4);       
-    }
-    */
-
     #[test]
     fn test_run_to_known_periods() {
         let input = Input::read("data_aoc2023/day20.txt");
-        let mut sim = Sim::new(&input);
+        let mut sim = Sim::new(&input).unwrap();
 
         let overall_period = sim.run_to_known_periods();
 
         assert!(sim.t < 4096);    
         for ss in sim.state_sets.iter() {
-            println!("Found period {}.", ss.period);
+            println!("Found period {}.", ss.get_period());
             println!("  active {} times", ss.active_times.len());
         }
         assert_eq!(overall_period, 226732077152351);
     }
     
-    /*
-    #[test]
-    fn test_rx() {
-        let input = Input::read("data_aoc2023/day20.txt");
-        let mut sim = Sim::new(&input);
-
-        let count = sim.run_to_rx();
-
-        assert_eq!(count, 0);
-    }
-    */
-
     #[test]
     fn test_part2() {
         let d = Day20::new("data_aoc2023/day20.txt");
@@ -1,8 +1,10 @@
-use std::{collections::HashSet, fs::File, io::{BufRead, BufReader}};
+use std::{collections::{HashSet, VecDeque}, fmt::{Display, Error, Formatter}, fs::File, io::{BufRead, BufReader}};
+#[cfg(test)]
 use strum::IntoEnumIterator;
+#[cfg(test)]
 use strum_macros::EnumIter;
 
-use crate::day::{Day, Answer};
+use crate::{day::{Day, Answer}, bitset::BitSet};
 
 struct Input {
     width: usize,
@@ -13,14 +15,16 @@ struct Input {
 
 impl Input {
     fn read(filename: &str) -> Input {
+        let f = File::open(filename).unwrap();
+        Input::parse(BufReader::new(f))
+    }
+
+    fn parse(reader: impl BufRead) -> Input {
         let mut width: usize = 0;
         let mut height: usize = 0;
         let mut start: (isize, isize) = (0, 0);
         let mut garden: HashSet<(isize, isize)> = HashSet::new();
 
-        let f = File::open(filename).unwrap();
-        let reader = BufReader::new(f);
-
         for (y, line) in reader.lines().enumerate() {
             let line = line.unwrap();
             width = line.len();
@@ -49,19 +53,189 @@ impl Input {
             height += 1;
         }
 
-        // We've baked in assumptions that height and width are the same and start is
-        // in the center.
-        assert_eq!(start.0 as usize, width/2);
-        assert_eq!(start.1 as usize, height/2);
-        assert_eq!(start.0, start.1);
-
         Input { width, height, start, garden }
 
     }
+
+    // The tile analysis bakes in that height and width are the same and start is
+    // in the center.
+    #[cfg(test)]
+    fn is_square_and_centered(&self) -> bool {
+        self.width == self.height &&
+            self.start.0 as usize == self.width/2 &&
+            self.start.1 as usize == self.height/2
+    }
+}
+
+// Fewest steps from the start to each plot, for a block of copies of the map
+// (tiles) around the one holding the start.  Tile (0, 0) is the original map,
+// and (tx, ty) runs from -radius to radius in each direction.
+struct TileDistances {
+    width: usize,
+    height: usize,
+    radius: isize,
+    // By tile, row by row, then by plot (y*width + x) within the tile.  None
+    // for rocks and for plots that can't be reached.
+    dist: Vec<Vec<Option<usize>>>,
 }
 
+impl TileDistances {
+    fn new(input: &Input, radius: isize) -> TileDistances {
+        let (w, h) = (input.width, input.height);
+        let span = (2*radius + 1) as usize;
+        let (block_w, block_h) = (span * w, span * h);
+
+        // Breadth-first search over the whole block at once.
+        let mut block: Vec<Option<usize>> = vec![None; block_w * block_h];
+        let mut queue: VecDeque<(usize, usize)> = VecDeque::new();
+
+        let start = (input.start.0 as usize + radius as usize * w, input.start.1 as usize + radius as usize * h);
+        block[start.1 * block_w + start.0] = Some(0);
+        queue.push_back(start);
+
+        while let Some((x, y)) = queue.pop_front() {
+            let d = block[y * block_w + x].unwrap();
+            for (nx, ny) in [(x+1, y), (x, y+1), (x.wrapping_sub(1), y), (x, y.wrapping_sub(1))] {
+                if nx >= block_w || ny >= block_h || block[ny * block_w + nx].is_some() {
+                    continue;
+                }
+                if input.garden.contains(&((nx % w) as isize, (ny % h) as isize)) {
+                    block[ny * block_w + nx] = Some(d + 1);
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        // Split the block up into tiles.
+        let mut dist: Vec<Vec<Option<usize>>> = Vec::new();
+        for ty in 0..span {
+            for tx in 0..span {
+                let mut tile: Vec<Option<usize>> = Vec::with_capacity(w * h);
+                for y in 0..h {
+                    let row = (ty*h + y) * block_w + tx*w;
+                    tile.extend_from_slice(&block[row..row + w]);
+                }
+                dist.push(tile);
+            }
+        }
+
+        TileDistances { width: w, height: h, radius, dist }
+    }
+
+    fn tile(&self, tx: isize, ty: isize) -> &[Option<usize>] {
+        let span = 2*self.radius + 1;
+        &self.dist[((ty + self.radius) * span + tx + self.radius) as usize]
+    }
+
+    // Whether tile 'to' is exactly 'stride' steps further away than tile 'from'
+    // for every plot.
+    fn shifted(&self, from: (isize, isize), to: (isize, isize), stride: usize) -> bool {
+        self.tile(from.0, from.1).iter()
+            .zip(self.tile(to.0, to.1))
+            .all(|(near, far)| *far == near.map(|d| d + stride))
+    }
+
+    // Whether the ring of tiles 'edge' away from the start is one map width
+    // (or height) further away than the ring inside it.  From there on out,
+    // each tile further away just adds another width or height.
+    fn settled(&self, edge: isize) -> bool {
+        (-edge..=edge).all(|t| {
+            self.shifted((edge-1, t), (edge, t), self.width) &&
+                self.shifted((1-edge, t), (-edge, t), self.width) &&
+                self.shifted((t, edge-1), (t, edge), self.height) &&
+                self.shifted((t, 1-edge), (t, -edge), self.height)
+        })
+    }
+}
 
-// Different classes of blocks, based on where the propagation started.
+#[derive(Debug, PartialEq, Eq)]
+enum GardenError {
+    // Distances to far tiles didn't settle into growing by a map width (or
+    // height) per tile within this many tiles of the start, so there's no
+    // pattern to extrapolate from.
+    Unsettled { tiles: usize },
+}
+
+impl Display for GardenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            GardenError::Unsettled { tiles } =>
+                write!(f, "distances don't settle within {tiles} tiles of the start"),
+        }
+    }
+}
+
+// Tiles out from the start to look for distances settling.
+const SETTLE_TILES: isize = 6;
+
+// Sum of floor((a*i + b)/m) for i in 0..n
+fn floor_sum(mut n: usize, mut m: usize, mut a: usize, mut b: usize) -> usize {
+    if n == 0 {
+        return 0;
+    }
+
+    let mut sum = 0;
+    loop {
+        if a >= m {
+            sum += n * (n-1) / 2 * (a / m);
+            a %= m;
+        }
+        if b >= m {
+            sum += n * (b / m);
+            b %= m;
+        }
+
+        let y_max = a * n + b;
+        if y_max < m {
+            break;
+        }
+        n = y_max / m;
+        b = y_max % m;
+        std::mem::swap(&mut m, &mut a);
+    }
+
+    sum
+}
+
+// Ways to pick n >= 0 so that n*stride fits in 'spare' steps, leaving an even
+// number over (the extra steps are spent stepping back and forth).
+fn count_line(spare: usize, stride: usize) -> usize {
+    (0..2)
+        .filter(|pn| (pn * stride) % 2 == spare % 2 && pn * stride <= spare)
+        .map(|pn| (spare - pn * stride) / (2 * stride) + 1)
+        .sum()
+}
+
+// Ways to pick n, m >= 0 so that n*stride_x + m*stride_y fits in 'spare'
+// steps, leaving an even number over.
+fn count_plane(spare: usize, stride_x: usize, stride_y: usize) -> usize {
+    let mut count = 0;
+
+    // Split on whether n and m are odd, then n = 2a + pn and m = 2b + pm.
+    for pn in 0..2 {
+        for pm in 0..2 {
+            let used = pn * stride_x + pm * stride_y;
+            if used % 2 != spare % 2 || used > spare {
+                continue;
+            }
+
+            // For each a up to a_max, there are floor(rest/(2*stride_y)) + 1 choices
+            // of b.  Counting a down from a_max makes it a floor_sum.
+            let rest = spare - used;
+            let a_max = rest / (2 * stride_x);
+            let slack = rest - a_max * 2 * stride_x;
+            count += a_max + 1 + floor_sum(a_max + 1, 2 * stride_y, 2 * stride_x, slack);
+        }
+    }
+
+    count
+}
+
+
+// Different classes of blocks, based on where the propagation started.  These
+// only work out for a square map with the start in the center, and clear paths
+// out from the start; num_by_distances doesn't need them.
+#[cfg(test)]
 #[derive(EnumIter, Debug)]
 enum FillFrom {
     // Start in the center
@@ -79,6 +253,7 @@ enum FillFrom {
     SouthEast,
 }
 
+#[cfg(test)]
 #[derive(EnumIter, Debug)]
 enum FillAmount {
     Least,                  // The tile on the leading edge of the propagation front
@@ -102,6 +277,7 @@ impl<'a> Day21<'a> {
         Self { input_filename: filename }
     }
 
+    #[cfg(test)]
     fn num_of(start: &FillFrom, amount: &FillAmount, steps: usize, input: &Input) -> usize {
         let w = input.width;
 
@@ -168,6 +344,7 @@ impl<'a> Day21<'a> {
         }
     }
 
+    #[cfg(test)]
     fn count_for(start: &FillFrom, amount: &FillAmount, steps: usize, input: &Input) -> usize {
         let w = input.width;
 
@@ -216,13 +393,15 @@ impl<'a> Day21<'a> {
             }
         };
         
-        let count_for = Day21::num_by_steps(sim_steps, input, &sim_start, false);
+        let count_for = Day21::num_by_bitsets(sim_steps, input, &sim_start, false);
         // println!("  count_for(start:{start:?}, amount:{amount:?}, steps:{sim_steps:?}) -> {count_for}");
 
         count_for
     }
 
+    #[cfg(test)]
     fn num_by_tile_analysis(steps: usize, input: &Input) -> usize {
+        assert!(input.is_square_and_centered());
         let mut sum = 0;
         
         for start in FillFrom::iter() {
@@ -239,6 +418,118 @@ impl<'a> Day21<'a> {
         sum
     }
 
+    // Plots reachable in exactly 'steps' steps on the infinite map.  A plot can
+    // be reached if its distance is no more than steps and has the same parity,
+    // since any spare steps can be spent stepping back and forth.
+    //
+    // Distances are found directly for a block of tiles around the start.  Once
+    // a ring of tiles is just one width (or height) further than the ring
+    // inside it, the tiles beyond continue that pattern: along the rows and
+    // columns through the ring each tile adds a width (or height), and in the
+    // corners beyond, each adds both.  Those are counted in closed form.
+    fn num_by_distances(steps: usize, input: &Input) -> Result<usize, GardenError> {
+        let distances = TileDistances::new(input, SETTLE_TILES + 1);
+
+        // Checking two rings guards against a coincidental match.
+        let edge = (1..SETTLE_TILES)
+            .find(|edge| distances.settled(*edge) && distances.settled(*edge + 1))
+            .ok_or(GardenError::Unsettled { tiles: SETTLE_TILES as usize })?;
+
+        let (w, h) = (input.width, input.height);
+        let mut sum = 0;
+
+        for ty in -edge..=edge {
+            for tx in -edge..=edge {
+                let on_x_edge = tx.abs() == edge;
+                let on_y_edge = ty.abs() == edge;
+
+                for d in distances.tile(tx, ty).iter().flatten() {
+                    if *d > steps {
+                        continue;
+                    }
+                    let spare = steps - d;
+
+                    sum += match (on_x_edge, on_y_edge) {
+                        (false, false) => 1 - spare % 2,
+                        (true, false) => count_line(spare, w),
+                        (false, true) => count_line(spare, h),
+                        (true, true) => count_plane(spare, w, h),
+                    };
+                }
+            }
+        }
+
+        Ok(sum)
+    }
+
+    // Same as num_by_steps, but with the occupied plots as a dense grid of bits,
+    // one BitSet per row.  The grid covers the map when it's bounded, or
+    // everything within 'steps' of the start when it repeats forever.
+    fn num_by_bitsets(steps: usize, input: &Input, start: &(isize, isize), infinite: bool) -> usize {
+        let (w, h) = (input.width as isize, input.height as isize);
+        let (x0, y0, cols, rows) = if infinite {
+            (start.0 - steps as isize, start.1 - steps as isize, 2*steps + 1, 2*steps + 1)
+        }
+        else {
+            (0, 0, input.width, input.height)
+        };
+
+        // Garden plots in each row of the grid.  Rows the map's height apart
+        // are the same, so there's only one per row of the map.
+        let garden: Vec<BitSet> = (0..h).map(|map_y| {
+            let mut row = BitSet::new(cols);
+            for col in 0..cols {
+                let map_x = (x0 + col as isize).rem_euclid(w);
+                row.set(col, input.garden.contains(&(map_x, map_y)));
+            }
+            row
+        }).collect();
+        let garden_row = |row: usize| &garden[(y0 + row as isize).rem_euclid(h) as usize];
+
+        // Occupied plots this step, last step, and the one being worked out.
+        let mut current: Vec<BitSet> = vec![BitSet::new(cols); rows];
+        let mut previous = current.clone();
+        let mut next = current.clone();
+
+        current[(start.1 - y0) as usize].insert((start.0 - x0) as usize);
+
+        for step in 0..steps {
+            // Nothing gets further than step+1 rows from the start.
+            let center = (start.1 - y0) as usize;
+            let first = center.saturating_sub(step + 1);
+            let last = (center + step + 1).min(rows - 1);
+
+            for row in first..=last {
+                let spread = &mut next[row];
+                spread.clear();
+                spread.union_shifted(&current[row], 1);
+                spread.union_shifted(&current[row], -1);
+                if row > 0 {
+                    spread.union_with(&current[row - 1]);
+                }
+                if row + 1 < rows {
+                    spread.union_with(&current[row + 1]);
+                }
+                spread.intersect_with(garden_row(row));
+            }
+
+            // On a bounded map the plots eventually flip back and forth between
+            // two sets.  Once they do, only the parity of what's left matters.
+            if !infinite && step > 0 && next == previous {
+                let left = steps - step - 1;
+                let last_set = if left.is_multiple_of(2) { &next } else { &current };
+                return last_set.iter().map(|row| row.count()).sum();
+            }
+
+            std::mem::swap(&mut previous, &mut current);
+            std::mem::swap(&mut current, &mut next);
+        }
+
+        current.iter().map(|row| row.count()).sum()
+    }
+
+    // The straightforward version, kept to check the others against.
+    #[cfg(test)]
     fn num_by_steps(steps: usize, input: &Input, start: &(isize, isize), infinite: bool) -> usize {
         // We're going to do let 'current' and 'next' swap between these maps.
         let mut set1: HashSet<(isize, isize)> = HashSet::new();
@@ -292,16 +583,18 @@ impl<'a> Day for Day21<'a> {
     fn part1(&self) -> Answer {
         let input = Input::read(self.input_filename);
 
-        let occupied = Day21::num_by_steps(64, &input, &input.start, false);
+        let occupied = Day21::num_by_bitsets(64, &input, &input.start, false);
 
         Answer::Numeric(occupied)
     }
 
     fn part2(&self) -> Answer {
         let input = Input::read(self.input_filename);
-        let answer = Day21::num_by_tile_analysis(26501365, &input);
 
-        Answer::Numeric(answer)
+        match Day21::num_by_distances(26501365, &input) {
+            Ok(answer) => Answer::Numeric(answer),
+            Err(e) => Answer::String(e.to_string()),
+        }
     }
 }
 
@@ -309,7 +602,7 @@ impl<'a> Day for Day21<'a> {
 mod test {
     use std::collections::HashSet;
 
-    use crate::day21::{Day21, Input, FillFrom, FillAmount};
+    use crate::day21::{Day21, Input, FillFrom, FillAmount, GardenError, count_line, count_plane, floor_sum};
 
     #[test]
     fn test_input() {
@@ -719,4 +1012,122 @@ mod test {
         assert!(answer < 620348631940729);
         assert_eq!(answer, 620348631910321);
     }
+
+    #[test]
+    fn test_counting() {
+        assert_eq!(floor_sum(0, 3, 1, 1), 0);
+        assert_eq!(floor_sum(5, 3, 2, 1), (0..5).map(|i| (2*i + 1)/3).sum());
+        assert_eq!(floor_sum(100, 7, 11, 13), (0..100).map(|i| (11*i + 13)/7).sum());
+
+        // Brute force over n (and m).
+        for spare in 0..60 {
+            for stride in 1..6 {
+                let line = (0..=spare/stride).filter(|n| (spare - n*stride) % 2 == 0).count();
+                assert_eq!(count_line(spare, stride), line);
+
+                for stride_y in 1..6 {
+                    let plane = (0..=spare/stride)
+                        .flat_map(|n| (0..=(spare - n*stride)/stride_y).map(move |m| n*stride + m*stride_y))
+                        .filter(|used| (spare - used) % 2 == 0)
+                        .count();
+                    assert_eq!(count_plane(spare, stride, stride_y), plane);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_num_by_distances() {
+        let input = Input::read("examples/day21_example1.txt");
+
+        for (steps, expected) in [(6, 16), (10, 50), (50, 1594), (100, 6536), (500, 167004), (1000, 668697), (5000, 16733044)] {
+            assert_eq!(Day21::num_by_distances(steps, &input), Ok(expected));
+        }
+    }
+
+    #[test]
+    fn test_rectangular_off_center() {
+        let input = Input::parse(concat!(
+            "..#.....#.\n",
+            ".S..#..#..\n",
+            "...##.....\n",
+            "#......#..\n",
+            "..........\n",
+            "....#.#.#.\n",
+        ).as_bytes());
+        assert_eq!((input.width, input.height), (10, 6));
+        assert_eq!(input.start, (1, 1));
+        assert!(!input.is_square_and_centered());
+
+        for steps in [0, 1, 2, 7, 20, 33, 64, 101] {
+            let expected = Day21::num_by_steps(steps, &input, &input.start, true);
+            assert_eq!(Day21::num_by_distances(steps, &input), Ok(expected), "{steps} steps");
+        }
+    }
+
+    #[test]
+    fn test_unsettled() {
+        // No row is clear all the way across, so crossing a tile sideways
+        // takes more steps than the map is wide.
+        let input = Input::parse(concat!(
+            "..#......\n",
+            ".S..#..#.\n",
+            "...##....\n",
+            "#......#.\n",
+            "....#....\n",
+        ).as_bytes());
+
+        let result = Day21::num_by_distances(100, &input);
+        assert_eq!(result, Err(GardenError::Unsettled { tiles: 6 }));
+        assert_eq!(result.unwrap_err().to_string(), "distances don't settle within 6 tiles of the start");
+    }
+
+    #[test]
+    fn test_num_by_bitsets() {
+        let input = Input::read("examples/day21_example1.txt");
+
+        for (steps, expected) in [(6, 16), (10, 50), (50, 1594), (100, 6536), (500, 167004), (1000, 668697)] {
+            assert_eq!(Day21::num_by_bitsets(steps, &input, &input.start, true), expected);
+        }
+
+        // Matches the straightforward version, both bounded and not, from
+        // anywhere on the map.
+        for start in [input.start, (0, 0), (10, 3), (4, 10)] {
+            for steps in [0, 1, 2, 3, 7, 16, 17, 40] {
+                for infinite in [false, true] {
+                    assert_eq!(Day21::num_by_bitsets(steps, &input, &start, infinite),
+                        Day21::num_by_steps(steps, &input, &start, infinite),
+                        "{steps} steps from {start:?}, infinite: {infinite}");
+                }
+            }
+        }
+
+        // Bounded, the plots settle down to flipping back and forth.
+        assert_eq!(Day21::num_by_bitsets(1001, &input, &input.start, false), Day21::num_by_bitsets(41, &input, &input.start, false));
+        assert_eq!(Day21::num_by_bitsets(1000, &input, &input.start, false), Day21::num_by_bitsets(40, &input, &input.start, false));
+    }
+
+    #[test]
+    fn test_num_by_bitsets_rectangular() {
+        let input = Input::parse(concat!(
+            "..#.....#.\n",
+            ".S..#..#..\n",
+            "...##.....\n",
+            "#......#..\n",
+            "....#.#.#.\n",
+        ).as_bytes());
+
+        for steps in [0, 1, 5, 12, 31, 70] {
+            for infinite in [false, true] {
+                assert_eq!(Day21::num_by_bitsets(steps, &input, &input.start, infinite),
+                    Day21::num_by_steps(steps, &input, &input.start, infinite));
+            }
+        }
+
+        // A start with nowhere to go
+        let boxed = Input::parse(".#.\n#S#\n.#.\n".as_bytes());
+        assert_eq!(Day21::num_by_bitsets(0, &boxed, &boxed.start, true), 1);
+        assert_eq!(Day21::num_by_bitsets(1, &boxed, &boxed.start, false), 0);
+        assert_eq!(Day21::num_by_bitsets(4, &boxed, &boxed.start, false), 0);
+    }
 }
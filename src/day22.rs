@@ -1,7 +1,9 @@
-use std::{collections::{HashMap, HashSet}, fs::File, io::{BufRead, BufReader}};
+use std::{collections::HashSet, fs::File, io::{BufRead, BufReader}};
 
 use crate::day::{Day, Answer};
 use regex::Regex;
+#[cfg(test)]
+use rgb::RGB8;
 
 // A basic 3D vector with isize components.
 #[derive(Debug, Eq, Hash, PartialEq)]
@@ -72,6 +74,14 @@ impl Input {
     }
 }
 
+// The top of the stack in one (x, y) column, while blocks are settling: the z
+// of the highest filled cell (0 for the ground) and the block filling it.
+#[derive(Clone, Copy, Debug)]
+struct Column {
+    top: isize,
+    block: Option<usize>,
+}
+
 // A stack of blocks.  This represents the state of the puzzle after all blocks have come to rest.
 struct Stack<'a> {
     input: &'a Input,
@@ -82,26 +92,33 @@ struct Stack<'a> {
     // A block's min Z will be this + 1.  Ground is Z=0.  The first block to fall will have base_height=0 so it's min Z is 1.
     base_height: Vec<isize>,              
 
-    // Which blocks occupy each coordinate V3D -> block id
-    occupied: HashMap<V3D, usize>,
-
     // Which blocks support a given one.
     // supports[block_id] -> a vector of block ids that support this one.
     supports: Vec<Vec<usize>>,
+
+    // Block ids in the order they were dropped (by initial z).  A block
+    // always comes after the blocks supporting it.
+    order: Vec<usize>,
+
+    // The nearest single block whose removal would make a given one fall,
+    // or None if only removing the ground would.  These form a tree (the
+    // dominator tree) with the ground at the root.
+    idom: Vec<Option<usize>>,
 }
 
 impl <'a> Stack<'a> {
     fn new(input: &'a Input) -> Stack<'a> {
-        // Create the stack with empty base_height, occupied and supports components.
-        let mut stack = Stack { input, base_height: Vec::new(), occupied: HashMap::new(), supports: Vec::new() };
+        // Create the stack with empty base_height and supports components.
+        let mut stack = Stack { input, base_height: Vec::new(), supports: Vec::new(),
+                                order: Vec::new(), idom: Vec::new() };
 
         // Run the "falling" process to allow all blocks to settle into supported positions.
-        // This sets the base_height and occupied components of Stack.
+        // This sets the base_height, supports and order components of Stack.
         stack.run();
 
-        // Analyze which blocks are supporting each block.
-        // This sets the .supports component of Stack
-        stack.gen_supports();
+        // Work out which blocks each block's support depends on.
+        // This sets the .idom component of Stack
+        stack.gen_dominators();
 
         // Voila, an initialized stack.
         stack
@@ -110,55 +127,51 @@ impl <'a> Stack<'a> {
     // Move one block into its settled position.
     // In order to settle the whole stack properly, blocks need to be dropped in order
     // from lowest to highest.
-    fn drop(&mut self, block_id: usize) {
+    fn drop(&mut self, block_id: usize, height_map: &mut [Vec<Column>]) {
         let block = &self.input.blocks[block_id];
 
-        // Start support_z at the initial height and reduce it until it reaches 0 or we find occupied blocks
-        let mut support_z: isize = block.initial_pos.z;
-        let mut supporters: usize = 0;
-
-        while support_z > 0 && (supporters == 0) {
-            // drop support_z by one
-            support_z -= 1;
-
-            // check occupancy for supporters
-            for x in block.initial_pos.x..block.initial_pos.x+block.size.x {
-                for y in block.initial_pos.y..block.initial_pos.y+block.size.y {
-                    if self.occupied.contains_key(&V3D::new(x, y, support_z)) {
-                        // Found a supporter
-                        supporters += 1;
-                    }
-                }
-            }
-        }
+        // The (x, y) columns under the block
+        let footprint = || (block.initial_pos.x..block.initial_pos.x+block.size.x)
+            .flat_map(move |x| (block.initial_pos.y..block.initial_pos.y+block.size.y)
+                .map(move |y| (x as usize, y as usize)));
+
+        // The block lands on the highest thing underneath it.
+        let support_z = footprint()
+            .map(|(x, y)| height_map[x][y].top)
+            .max()
+            .unwrap();
+
+        // Every block whose top is at that height is supporting it.
+        let mut supporters: Vec<usize> = footprint()
+            .filter(|(x, y)| height_map[*x][*y].top == support_z)
+            .filter_map(|(x, y)| height_map[x][y].block)
+            .collect();
+        supporters.sort();
+        supporters.dedup();
 
         // Record the support Z for each block.  (The block's Z is this +1.)
         // println!("Found support at z={support_z} by {:?}", supporters);
         self.base_height[block_id] = support_z;
+        self.supports[block_id] = supporters;
 
-        // Record this block's occupancy
-        for x in block.initial_pos.x..block.initial_pos.x+block.size.x {
-            for y in block.initial_pos.y..block.initial_pos.y+block.size.y {
-                for z in support_z+1..support_z+block.size.z+1 {
-                    // println!("Block {block_id} occupying ({x}, {y}, {z})");
-                    assert!(!self.occupied.contains_key(&V3D::new(x, y, z)));
-                    self.occupied.insert(V3D::new(x, y, z), block_id);
-                }
-            }
-        }        
+        // Now this block is the top of its columns.
+        for (x, y) in footprint() {
+            height_map[x][y] = Column { top: support_z + block.size.z, block: Some(block_id) };
+        }
     }
 
     fn run(&mut self) {
-        self.occupied.clear();
+        let blocks = &self.input.blocks;
+        self.base_height = vec![0; blocks.len()];
+        self.supports = vec![Vec::new(); blocks.len()];
 
-        // Initialize base_height vector with initial Z positions - 1
-        self.base_height.clear();
-        for n in 0..self.input.blocks.len() {
-            self.base_height.push(self.input.blocks.get(n).unwrap().initial_pos.z - 1);
-        }
+        // Only the top of each column matters, so that's all there is to keep.
+        let x_size = blocks.iter().map(|b| b.initial_pos.x + b.size.x).max().unwrap_or(0) as usize;
+        let y_size = blocks.iter().map(|b| b.initial_pos.y + b.size.y).max().unwrap_or(0) as usize;
+        let mut height_map = vec![vec![Column { top: 0, block: None }; y_size]; x_size];
 
         // Sort blocks by initial z, lowest to highest, creating a vector of (z, block_id)
-        let mut block_ids = self.input.blocks.iter()
+        let mut block_ids = blocks.iter()
             .enumerate()
             .map(|(block_id, block)| {
                 (block.initial_pos.z, block_id)
@@ -167,37 +180,49 @@ impl <'a> Stack<'a> {
         block_ids.sort();
 
         // Go through all blocks, in Z order from 0 to max, dropping each one.
-        for (_height, block_id) in &block_ids {
-            self.drop(*block_id);
+        // A block only lands on blocks that started lower, and those were dropped
+        // first, so this drop order has every block after the blocks supporting it.
+        // It isn't sorted by settled height, though.
+        self.order = block_ids.iter().map(|(_height, block_id)| *block_id).collect();
+        for block_id in self.order.clone() {
+            self.drop(block_id, &mut height_map);
         }
     }
 
-    // Find and record all the blocks supporting each block.
-    // self.supports[supported block id: usize] -> Vec<supporting block id: usize>
-    fn gen_supports(&mut self) {
-        // For each block, generate a vector of blocks it rests on.
-        for block_id in 0..self.input.blocks.len() {
-            let block = &self.input.blocks[block_id];
-
-            let mut blocks_below: HashSet<usize> = HashSet::new();
-            let x0 = block.initial_pos.x;
-            let y0 = block.initial_pos.y;
-            let z0 = self.base_height.get(block_id).unwrap()+1;
+    // Find the immediate dominator of each block: the closest block that all
+    // of its support passes through.  Going through the blocks in drop order,
+    // that's the closest common ancestor in the tree of all the blocks
+    // supporting it.
+    fn gen_dominators(&mut self) {
+        let n = self.input.blocks.len();
+        self.idom = vec![None; n];
+
+        // Steps down the tree to the ground.  Blocks on the ground are at depth 1.
+        let mut depth: Vec<usize> = vec![0; n];
+        let depth_of = |depth: &Vec<usize>, block: Option<usize>| block.map_or(0, |b| depth[b]);
+
+        for block_id in self.order.clone() {
+            if self.base_height[block_id] == 0 {
+                depth[block_id] = 1;
+                continue;
+            }
 
-            for dx in 0..block.size.x {
-                for dy in 0..block.size.y {
-                    if let Some(block_below) = self.occupied.get(&V3D::new(x0+dx, y0+dy, z0-1)) {
-                        blocks_below.insert(*block_below);
+            let mut common = Some(self.supports[block_id][0]);
+            for supporter in &self.supports[block_id][1..] {
+                let mut other = Some(*supporter);
+                while common != other {
+                    let (dc, d_other) = (depth_of(&depth, common), depth_of(&depth, other));
+                    if dc >= d_other {
+                        common = self.idom[common.unwrap()];
+                    }
+                    if d_other >= dc {
+                        other = self.idom[other.unwrap()];
                     }
                 }
             }
 
-            // Convert set to Vec and store it.
-            self.supports.push(blocks_below.iter()
-                .map(|n| { *n })
-                .collect());
-
-            // println!("Block {block_id} supported by {:?}", self.supports[block_id]);
+            self.idom[block_id] = common;
+            depth[block_id] = depth_of(&depth, common) + 1;
         }
     }
 
@@ -216,62 +241,229 @@ impl <'a> Stack<'a> {
         self.input.blocks.len() - id_set.len()
     }
 
+    // Add up all the fall set sizes over all the blocks.
+    fn total_would_fall(&self) -> usize {
+        // A block falls when any one of the blocks dominating it is removed,
+        // so each block is counted once for each of those.
+        (0..self.input.blocks.len())
+            .map(|block_id| self.critical_for(block_id).len())
+            .sum()
+    }
+
+    // Single blocks that would make this one fall if removed, nearest first.
+    fn critical_for(&self, block_id: usize) -> Vec<usize> {
+        let mut critical: Vec<usize> = Vec::new();
+        let mut next = self.idom[block_id];
+
+        while let Some(dominator) = next {
+            critical.push(dominator);
+            next = self.idom[dominator];
+        }
+
+        critical
+    }
+}
+
+// Queries on the settled stack
+#[cfg(test)]
+impl <'a> Stack<'a> {
+    // Blocks directly underneath and touching this one
+    fn rests_on(&self, block_id: usize) -> &[usize] {
+        &self.supports[block_id]
+    }
+
+    // Blocks directly on top of and touching this one
+    fn holds_up(&self, block_id: usize) -> Vec<usize> {
+        (0..self.input.blocks.len())
+            .filter(|other| self.supports[*other].contains(&block_id))
+            .collect()
+    }
+
+    // Blocks that would fall if all of the removed ones were taken out at
+    // once, not counting the removed ones themselves.
+    fn fall_set(&self, removed: &[usize]) -> Vec<usize> {
+        let mut falling: Vec<bool> = vec![false; self.input.blocks.len()];
+        for block_id in removed {
+            falling[*block_id] = true;
+        }
+
+        // In drop order, every block supporting a block is settled before
+        // it's looked at.
+        let mut fallen: Vec<usize> = Vec::new();
+        for block_id in &self.order {
+            if falling[*block_id] || self.base_height[*block_id] == 0 {
+                continue;
+            }
+
+            if self.supports[*block_id].iter().all(|supporter| falling[*supporter]) {
+                falling[*block_id] = true;
+                fallen.push(*block_id);
+            }
+        }
+
+        fallen.sort();
+        fallen
+    }
+
     fn fall_set_size(&self, block_id: usize) -> usize {
-        let mut would_fall: HashSet<usize> = HashSet::new();
-        would_fall.insert(block_id);
-        // println!("What happens if {block_id} removed?");
-
-        // Iterate until no blocks fall any more.
-        let mut falls = true;
-        while falls {
-            // Will reset to true and keep the loop going if any new falls found on this iteration.
-            falls = false;  
-
-            for test_block in 0..self.input.blocks.len() {
-                // We already decided this block would fall, skip it.
-                if would_fall.contains(&test_block) { continue; }
-
-                // Test block is on the ground, it can't fall.
-                if self.base_height[test_block] == 0 { continue; }
-
-                // A block is supported if one of its supports is not in the would_fall set.
-                let supported = self.supports[test_block].iter()
-                    .map(|supporter| { 
-                        // println!("Is supporter {supporter} still ok? {}", !would_fall.contains(supporter) );
-                        !would_fall.contains(supporter)
-                    })
-                    .fold(false, |accum, supported| { accum || supported });
-
-                // println!("  {test_block} supported? {supported}");
-
-                // If the test block is unsupported, record it as fallen
-                if !supported {
-                    would_fall.insert(test_block);
-                    falls = true;
-                }
+        self.fall_set(&[block_id]).len()
+    }
+
+    // The block closest to this one that all its support passes through
+    fn immediate_dominator(&self, block_id: usize) -> Option<usize> {
+        self.idom[block_id]
+    }
+
+    // The dominator tree as text, one block id per line, indented under the
+    // nearest block it depends on.  Blocks that only depend on the ground are
+    // at the left.
+    fn dominator_tree(&self) -> String {
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); self.input.blocks.len()];
+        let mut roots: Vec<usize> = Vec::new();
+        for block_id in 0..self.input.blocks.len() {
+            match self.idom[block_id] {
+                Some(dominator) => children[dominator].push(block_id),
+                None => roots.push(block_id),
             }
         }
 
-        // println!("Removing {block_id}, these fall: {would_fall:?}");
+        let mut text = String::new();
+        let mut pending: Vec<(usize, usize)> = roots.iter().rev().map(|root| (*root, 0)).collect();
+        while let Some((block_id, indent)) = pending.pop() {
+            text += &format!("{}{block_id}\n", "  ".repeat(indent));
+            for child in children[block_id].iter().rev() {
+                pending.push((*child, indent + 1));
+            }
+        }
 
-        // The falling has settled, return the size of the would_fall hash set minus 1.
-        // (Minus 1 is because we seeded would_fall with block_id but we don't want to count that one)
-        would_fall.len() - 1
-        
+        text
     }
+}
 
-    // Add up all the fall set sizes over all the blocks.
-    fn total_would_fall(&self) -> usize {
-        // Start from the top of the stack, create sets of blocks that would fall if
-        // a given block would fall.  For a given block, determine all directly supported
-        // block ids, then get the union of their sets.
-        let mut total = 0;
+// Which horizontal axis a side view runs along
+#[cfg(test)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Axis {
+    X,
+    Y,
+}
 
+// Output of the settled stack
+#[cfg(test)]
+impl <'a> Stack<'a> {
+    // Lowest corner of the block once settled, and the corner diagonally
+    // opposite (exclusive).
+    fn bounds(&self, block_id: usize) -> (V3D, V3D) {
+        let block = &self.input.blocks[block_id];
+        let low = V3D::new(block.initial_pos.x, block.initial_pos.y, self.base_height[block_id] + 1);
+        let high = low.plus(&block.size);
+        (low, high)
+    }
+
+    // For each block, how many others would fall if it were removed
+    fn fall_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.input.blocks.len()];
         for block_id in 0..self.input.blocks.len() {
-            total += self.fall_set_size(block_id);
+            for dominator in self.critical_for(block_id) {
+                counts[dominator] += 1;
+            }
+        }
+        counts
+    }
+
+    // Blue for blocks nothing depends on, through to red for the block the
+    // most others depend on.
+    fn fall_color(count: usize, max_count: usize) -> RGB8 {
+        let t = if max_count == 0 { 0.0 } else { count as f64 / max_count as f64 };
+        RGB8 { r: (255.0 * t).round() as u8, g: 64, b: (255.0 * (1.0 - t)).round() as u8 }
+    }
+
+    // The settled stack as an ASCII PLY mesh: a box per block, coloured by
+    // how many blocks would fall if it were removed.
+    fn to_ply(&self) -> String {
+        let counts = self.fall_counts();
+        let max_count = counts.iter().copied().max().unwrap_or(0);
+        let n = self.input.blocks.len();
+
+        let mut ply = String::from("ply\nformat ascii 1.0\n");
+        ply += &format!("element vertex {}\n", 8 * n);
+        ply += "property int x\nproperty int y\nproperty int z\n";
+        ply += "property uchar red\nproperty uchar green\nproperty uchar blue\n";
+        ply += &format!("element face {}\n", 6 * n);
+        ply += "property list uchar int vertex_indices\nend_header\n";
+
+        // Corners are numbered by which of x, y, z (bits 0, 1, 2) are at the high side.
+        for (block_id, count) in counts.iter().enumerate() {
+            let (low, high) = self.bounds(block_id);
+            let color = Self::fall_color(*count, max_count);
+            for corner in 0..8 {
+                let x = if corner & 1 == 0 { low.x } else { high.x };
+                let y = if corner & 2 == 0 { low.y } else { high.y };
+                let z = if corner & 4 == 0 { low.z } else { high.z };
+                ply += &format!("{x} {y} {z} {} {} {}\n", color.r, color.g, color.b);
+            }
+        }
+
+        // Faces wind counterclockwise seen from outside the box.
+        const FACES: [[usize; 4]; 6] = [
+            [0, 2, 3, 1], [4, 5, 7, 6],     // bottom, top
+            [0, 1, 5, 4], [2, 6, 7, 3],     // low y, high y
+            [0, 4, 6, 2], [1, 3, 7, 5],     // low x, high x
+        ];
+        for block_id in 0..n {
+            for face in FACES {
+                let [a, b, c, d] = face.map(|corner| 8 * block_id + corner);
+                ply += &format!("4 {a} {b} {c} {d}\n");
+            }
         }
 
-        total
+        ply
+    }
+
+    // The settled stack seen from the side, as drawn in the puzzle: blocks
+    // are lettered A to Z (then round again), ? where more than one block is
+    // in line, and the ground is at the bottom.
+    fn side_view(&self, axis: Axis) -> String {
+        let n = self.input.blocks.len();
+        let along = |v: &V3D| if axis == Axis::X { v.x } else { v.y };
+
+        let width = (0..n).map(|block_id| along(&self.bounds(block_id).1)).max().unwrap_or(0) as usize;
+        let height = (0..n).map(|block_id| self.bounds(block_id).1.z - 1).max().unwrap_or(0) as usize;
+
+        // What's seen in each cell, by z then position along the axis
+        let mut seen: Vec<Vec<Option<usize>>> = vec![vec![None; width]; height + 1];
+        let mut crowded: Vec<Vec<bool>> = vec![vec![false; width]; height + 1];
+        for block_id in 0..n {
+            let (low, high) = self.bounds(block_id);
+            for z in low.z..high.z {
+                for a in along(&low)..along(&high) {
+                    let cell = &mut seen[z as usize][a as usize];
+                    if cell.is_some_and(|other| other != block_id) {
+                        crowded[z as usize][a as usize] = true;
+                    }
+                    *cell = Some(block_id);
+                }
+            }
+        }
+
+        let name = if axis == Axis::X { 'x' } else { 'y' };
+        let mut view = format!("{}{name}\n", " ".repeat(width / 2));
+        view += &(0..width).map(|a| char::from_digit((a % 10) as u32, 10).unwrap()).collect::<String>();
+        view += "\n";
+
+        for z in (1..=height).rev() {
+            for a in 0..width {
+                view.push(match seen[z][a] {
+                    _ if crowded[z][a] => '?',
+                    Some(block_id) => (b'A' + (block_id % 26) as u8) as char,
+                    None => '.',
+                });
+            }
+            view += &format!(" {z}\n");
+        }
+        view += &format!("{} 0\n", "-".repeat(width));
+
+        view
     }
 }
 
@@ -303,7 +495,7 @@ impl<'a> Day for Day22<'a> {
 
 #[cfg(test)]
 mod test {
-    use crate::day22::{Day22, Input, Block, Stack};
+    use crate::day22::{Day22, Input, Block, Stack, V3D, Axis};
     use crate::{Answer, Day};
 
     #[test]
@@ -395,4 +587,132 @@ mod test {
 
         assert_eq!(d.part1(), Answer::Numeric(389));
     }
+
+    #[test]
+    fn test_supports() {
+        let input = Input::read("examples/day22_example1.txt");
+        let stack = Stack::new(&input);
+
+        // A holds up B and C, which both hold up D and E, and so on.
+        assert_eq!(stack.rests_on(0), &[] as &[usize]);
+        assert_eq!(stack.holds_up(0), &[1, 2]);
+        assert_eq!(stack.rests_on(3), &[1, 2]);
+        assert_eq!(stack.holds_up(1), &[3, 4]);
+        assert_eq!(stack.rests_on(5), &[3, 4]);
+        assert_eq!(stack.holds_up(5), &[6]);
+        assert_eq!(stack.holds_up(6), &[] as &[usize]);
+    }
+
+    #[test]
+    fn test_fall_set() {
+        let input = Input::read("examples/day22_example1.txt");
+        let stack = Stack::new(&input);
+
+        assert_eq!(stack.fall_set(&[0]), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(stack.fall_set(&[1]), vec![]);
+        assert_eq!(stack.fall_set(&[1, 2]), vec![3, 4, 5, 6]);
+        assert_eq!(stack.fall_set(&[3, 4]), vec![5, 6]);
+        assert_eq!(stack.fall_set(&[2, 3]), vec![]);
+        assert_eq!(stack.fall_set(&[5]), vec![6]);
+        assert_eq!(stack.fall_set(&[]), vec![]);
+        assert_eq!(stack.fall_set_size(0), 6);
+    }
+
+    #[test]
+    fn test_dominators() {
+        let input = Input::read("examples/day22_example1.txt");
+        let stack = Stack::new(&input);
+
+        assert_eq!(stack.immediate_dominator(0), None);
+        assert_eq!(stack.immediate_dominator(3), Some(0));
+        assert_eq!(stack.immediate_dominator(6), Some(5));
+        assert_eq!(stack.critical_for(6), vec![5, 0]);
+        assert_eq!(stack.critical_for(4), vec![0]);
+        assert_eq!(stack.dominator_tree(), "0\n  1\n  2\n  3\n  4\n  5\n    6\n");
+
+        // Removing a block drops exactly the blocks it dominates.
+        for example in ["examples/day22_example1.txt", "examples/day22_example2.txt"] {
+            let input = Input::read(example);
+            let stack = Stack::new(&input);
+
+            for block_id in 0..input.blocks.len() {
+                let dominated: Vec<usize> = (0..input.blocks.len())
+                    .filter(|other| stack.critical_for(*other).contains(&block_id))
+                    .collect();
+                assert_eq!(stack.fall_set(&[block_id]), dominated);
+            }
+        }
+    }
+
+    #[test]
+    fn test_settling() {
+        let input = Input { blocks: vec![
+            Block::new(V3D::new(0, 0, 1), V3D::new(0, 0, 3)),     // standing upright
+            Block::new(V3D::new(2, 0, 5), V3D::new(2, 0, 5)),     // a cube, off to the side
+            Block::new(V3D::new(0, 0, 10), V3D::new(2, 0, 10)),   // across both
+            Block::new(V3D::new(1, 0, 12), V3D::new(1, 2, 12)),   // across the middle of that
+            Block::new(V3D::new(0, 2, 20), V3D::new(2, 2, 20)),
+        ]};
+        let stack = Stack::new(&input);
+
+        // The cube falls to the ground, and the bar lands on the tall block only.
+        assert_eq!(stack.base_height, vec![0, 0, 3, 4, 5]);
+        assert_eq!(stack.rests_on(2), &[0]);
+        assert_eq!(stack.rests_on(3), &[2]);
+        assert_eq!(stack.rests_on(4), &[3]);
+        assert_eq!(stack.holds_up(1), &[] as &[usize]);
+        assert_eq!(stack.fall_set(&[0]), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_side_view() {
+        let input = Input::read("examples/day22_example1.txt");
+        let stack = Stack::new(&input);
+
+        assert_eq!(stack.side_view(Axis::X), concat!(
+            " x\n",
+            "012\n",
+            ".G. 6\n",
+            ".G. 5\n",
+            "FFF 4\n",
+            "D.E 3\n",
+            "??? 2\n",
+            ".A. 1\n",
+            "--- 0\n",
+        ));
+        assert_eq!(stack.side_view(Axis::Y), concat!(
+            " y\n",
+            "012\n",
+            ".G. 6\n",
+            ".G. 5\n",
+            ".F. 4\n",
+            "??? 3\n",
+            "B.C 2\n",
+            "AAA 1\n",
+            "--- 0\n",
+        ));
+    }
+
+    #[test]
+    fn test_ply() {
+        let input = Input::read("examples/day22_example1.txt");
+        let stack = Stack::new(&input);
+
+        assert_eq!(stack.fall_counts(), vec![6, 0, 0, 0, 0, 1, 0]);
+
+        let ply = stack.to_ply();
+        let lines: Vec<&str> = ply.lines().collect();
+        let header_end = lines.iter().position(|line| *line == "end_header").unwrap();
+        assert!(lines.contains(&"element vertex 56"));
+        assert!(lines.contains(&"element face 42"));
+        assert_eq!(lines.len(), header_end + 1 + 56 + 42);
+
+        // A is on the ground and holds everything up, so it's red.
+        assert_eq!(lines[header_end + 1], "1 0 1 255 64 0");
+        assert_eq!(lines[header_end + 8], "2 3 2 255 64 0");
+        // B holds nothing up alone, so it's blue.
+        assert_eq!(lines[header_end + 9], "0 0 2 0 64 255");
+        assert_eq!(lines[header_end + 1 + 56], "4 0 2 3 1");
+        assert_eq!(*lines.last().unwrap(), "4 49 51 55 53");
+    }
 }
@@ -1,5 +1,5 @@
 use crate::day::{Day, Answer, LineBasedInput};
-use std::fs::File;
+use std::{collections::HashMap, fs::File};
 
 struct Line {
     text: String,
@@ -19,13 +19,82 @@ struct Sym {
     row: usize  // TODO-DW
 }
 
+// What's at a position in the schematic, by index into Input's numbers or symbols
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cell {
+    Number(usize),
+    Symbol(usize),
+}
+
 struct Input {
     symbols: Vec<Sym>,
     numbers: Vec<Number>,
+
+    // (row, col) -> what's there, for every digit and symbol
+    index: HashMap<(usize, usize), Cell>,
 }
 
 impl Input {
+    fn new(symbols: Vec<Sym>, numbers: Vec<Number>) -> Input {
+        let mut index: HashMap<(usize, usize), Cell> = HashMap::new();
+
+        for (id, sym) in symbols.iter().enumerate() {
+            index.insert((sym.row, sym.col), Cell::Symbol(id));
+        }
+        for (id, number) in numbers.iter().enumerate() {
+            for col in number.col..number.col+number.len {
+                index.insert((number.row, col), Cell::Number(id));
+            }
+        }
+
+        Input { symbols, numbers, index }
+    }
+
+    // Everything touching a run of len cells starting at (row, col), including
+    // diagonally, each only once.
+    fn around(&self, row: usize, col: usize, len: usize) -> Vec<Cell> {
+        let mut cells: Vec<Cell> = Vec::new();
+
+        for r in row.saturating_sub(1)..=row+1 {
+            for c in col.saturating_sub(1)..=col+len {
+                if r == row && c >= col && c < col+len {
+                    // Part of the run itself
+                    continue;
+                }
+                if let Some(cell) = self.index.get(&(r, c)) {
+                    if !cells.contains(cell) {
+                        cells.push(*cell);
+                    }
+                }
+            }
+        }
+
+        cells
+    }
+
+    // The numbers next to a symbol
+    fn numbers_next_to(&self, sym: &Sym) -> Vec<&Number> {
+        self.around(sym.row, sym.col, 1).iter()
+            .filter_map(|cell| match cell {
+                Cell::Number(id) => Some(&self.numbers[*id]),
+                Cell::Symbol(_) => None,
+            })
+            .collect()
+    }
+
+    // The symbols next to a number
+    fn symbols_next_to(&self, number: &Number) -> Vec<&Sym> {
+        self.around(number.row, number.col, number.len).iter()
+            .filter_map(|cell| match cell {
+                Cell::Symbol(id) => Some(&self.symbols[*id]),
+                Cell::Number(_) => None,
+            })
+            .collect()
+    }
+
     // determine if a particular number is adjacent to a particular symbol
+    // (the index does this now; kept to check it against)
+    #[cfg(test)]
     fn adjacent(&self, number: &Number, sym: &Sym) -> bool {
         let mut is_adjacent = false;
         let target_c1 = number.col;
@@ -44,41 +113,41 @@ impl Input {
 
     // A number is a part number if adjacent to some symbol.
     fn is_part_number(&self, candidate: &Number)-> bool {
-        let mut is = false;
-        for sym in &self.symbols {
-            if self.adjacent(candidate, sym) {
-                is = true;
-                break;
-            }
-        }
-
-        is
+        !self.symbols_next_to(candidate).is_empty()
     }
 
     // Check if a symbol is a gear (a '*' adjacent to exactly two part numbers)
     // If so, return Some(gear ratio) else None
     fn gear_ratio(&self, sym: &Sym) -> Option<usize> {
-        if sym.c == '*' {
-            // Get a vector of all numbers adjacent to this symbol
-            let adj_numbers: Vec<&Number> = self.numbers.iter()
-                .filter(|n| self.adjacent(n, sym))
-                .collect();
-
-            if adj_numbers.len() == 2 {
-                // We have exactly two adjacent numbers
-                Some(adj_numbers[0].number * adj_numbers[1].number)
-            }
-            else {
-                // Wrong number of adjacent numbers
-                None
-            }
+        self.gear_value(sym, '*', 2)
+    }
+
+    // Generalised gears: a symbol c adjacent to exactly count numbers.
+    // If so, return Some(product of the numbers) else None
+    fn gear_value(&self, sym: &Sym, c: char, count: usize) -> Option<usize> {
+        if sym.c != c {
+            // Wrong symbol, this isn't a gear.
+            return None;
+        }
+
+        let adj_numbers = self.numbers_next_to(sym);
+        if adj_numbers.len() == count {
+            Some(adj_numbers.iter().map(|n| n.number).product())
         }
         else {
-            // Symbol isn't '*', this isn't a gear.
+            // Wrong number of adjacent numbers
             None
         }
     }
 
+    // All the symbols c with exactly count numbers next to them
+    #[cfg(test)]
+    fn gears(&self, c: char, count: usize) -> Vec<&Sym> {
+        self.symbols.iter()
+            .filter(|sym| self.gear_value(sym, c, count).is_some())
+            .collect()
+    }
+
     fn sum_part_numbers(&self) -> usize {
         self.numbers.iter()
             .filter(|n| self.is_part_number(n))
@@ -161,7 +230,7 @@ impl<'a> Day3<'a> {
             }
         }
         
-        Input { symbols, numbers }
+        Input::new(symbols, numbers)
     }
 
 
@@ -193,9 +262,44 @@ impl<'a> Day for Day3<'a> {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
     use crate::{Day, Answer, Day3};
+    use crate::day3::{Input, Cell};
     // use crate::day3::Record;
 
+    // Terminal escapes to show numbers that aren't part numbers in reverse video
+    const HIGHLIGHT: &str = "\x1b[7m";
+    const RESET: &str = "\x1b[0m";
+
+    // The schematic, with numbers that aren't part numbers highlighted.
+    fn render(input: &Input, text: &str) -> String {
+        let not_parts: HashSet<usize> = (0..input.numbers.len())
+            .filter(|id| !input.is_part_number(&input.numbers[*id]))
+            .collect();
+        let mut out = String::new();
+
+        for (row, line) in text.lines().enumerate() {
+            for (col, c) in line.chars().enumerate() {
+                let number = match input.index.get(&(row, col)) {
+                    Some(Cell::Number(id)) if not_parts.contains(id) => Some(&input.numbers[*id]),
+                    _ => None,
+                };
+
+                match number {
+                    Some(n) => {
+                        if col == n.col { out += HIGHLIGHT; }
+                        out.push(c);
+                        if col == n.col + n.len - 1 { out += RESET; }
+                    }
+                    None => out.push(c),
+                }
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
     #[test]
     fn test_input_p1() {        
         let d = Day3::new("examples/day3_example1.txt");
@@ -246,4 +350,61 @@ mod tests {
         let d = Day3::new("examples/day3_example1.txt");
         assert_eq!(d.part2(), Answer::Numeric(467835));
     }
+
+    #[test]
+    fn test_neighbours() {
+        let d = Day3::new("examples/day3_example1.txt");
+        let input = d.read_input(true);
+
+        // The first '*' touches 467 and 35.
+        let star = &input.symbols[0];
+        assert_eq!((star.c, star.row, star.col), ('*', 1, 3));
+        let numbers: Vec<usize> = input.numbers_next_to(star).iter().map(|n| n.number).collect();
+        assert_eq!(numbers, vec![467, 35]);
+
+        // 617 only touches the '*' after it, and 114 touches nothing.
+        let n617 = input.numbers.iter().find(|n| n.number == 617).unwrap();
+        let syms: Vec<char> = input.symbols_next_to(n617).iter().map(|s| s.c).collect();
+        assert_eq!(syms, vec!['*']);
+        let n114 = input.numbers.iter().find(|n| n.number == 114).unwrap();
+        assert!(input.symbols_next_to(n114).is_empty());
+
+        // The index agrees with checking every pair.
+        for number in &input.numbers {
+            for sym in &input.symbols {
+                let indexed = input.symbols_next_to(number).iter().any(|s| std::ptr::eq(*s, sym));
+                assert_eq!(indexed, input.adjacent(number, sym));
+            }
+        }
+    }
+
+    #[test]
+    fn test_gears() {
+        let d = Day3::new("examples/day3_example1.txt");
+        let input = d.read_input(true);
+
+        assert_eq!(input.gears('*', 2).len(), 2);
+        assert_eq!(input.gears('*', 1).len(), 1);
+        assert_eq!(input.gears('#', 1).len(), 1);
+        assert_eq!(input.gears('+', 2).len(), 0);
+
+        let single: Vec<usize> = input.gears('*', 1).iter()
+            .filter_map(|sym| input.gear_value(sym, '*', 1))
+            .collect();
+        assert_eq!(single, vec![617]);
+    }
+
+    #[test]
+    fn test_render() {
+        let d = Day3::new("examples/day3_example1.txt");
+        let input = d.read_input(true);
+
+        let text = std::fs::read_to_string("examples/day3_example1.txt").unwrap();
+        let rendered = render(&input, &text);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], format!("467..{HIGHLIGHT}114{RESET}.."));
+        assert_eq!(lines[5], format!(".....+.{HIGHLIGHT}58{RESET}."));
+        assert_eq!(lines[9], ".664.598..");
+    }
 }
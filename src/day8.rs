@@ -4,6 +4,7 @@ use regex::Regex;
 use num::integer::lcm;
 
 use crate::day::{Day, Answer};
+use crate::cycle;
 
 struct NodeInfo {
     left: String,
@@ -121,51 +122,28 @@ impl<'a> Day8<'a> {
 
     // returns (period_start, period_len, endings_per_period, last_ending)
     fn periodicity(&self, input: &Input, start: usize) -> (usize, usize, usize, usize) {
-        let mut steps = 0;
-        let mut endings = 0;
-        let mut last_ending = 0;
-        let mut dir_index = 0;
-        let mut loc = start;
-
-        // (node_no, dir_index) -> (steps, endings)
-        let mut history: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
-        let mut period_len = 0;
-        let mut period_start = 0;
-        let mut prior_endings: usize;
-        let mut endings_per_period = 0;
-
-
-        let mut repeating = false;
-        while !repeating {
-
-            history.insert((loc, dir_index), (steps, endings));
-
-            steps += 1;
-
-
-            loc = match input.directions[dir_index] {
-                'L' => {
-                    input.node_map[loc].0
-                }
-                'R' => {
-                    input.node_map[loc].1
-                }
+        // State is (node_no, dir_index)
+        let history = cycle::with_history((start, 0), |(loc, dir_index)| {
+            let next = match input.directions[*dir_index] {
+                'L' => input.node_map[*loc].0,
+                'R' => input.node_map[*loc].1,
                 _ => panic!(),
             };
+            (next, (dir_index+1) % input.directions.len())
+        });
+        let period_start = history.cycle.prefix;
+        let period_len = history.cycle.period;
 
+        // Count endings over steps 1 through the first repeat, and over the last period.
+        let mut endings_per_period = 0;
+        let mut last_ending = 0;
+        for step in 1..=period_start+period_len {
+            let (loc, _) = *history.state_at(step);
             if input.nodes[loc].is_ghost_end {
-                endings += 1;
                 last_ending = loc;
-            }
-
-            dir_index = (dir_index+1) % input.directions.len();
-
-            if history.contains_key(&(loc, dir_index)) {
-
-                (period_start, prior_endings) = history[&(loc, dir_index)];
-                period_len = steps - period_start;
-                endings_per_period = endings - prior_endings;
-                repeating = true;
+                if step > period_start {
+                    endings_per_period += 1;
+                }
             }
         }
 
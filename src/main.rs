@@ -1,4 +1,7 @@
 mod day;
+mod bitset;
+mod cycle;
+mod polygon;
 mod day0;
 mod day1;
 mod day2;
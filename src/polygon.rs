@@ -0,0 +1,161 @@
+use num::integer::gcd;
+
+// Simple polygons whose corners are on integer grid points.
+//
+// The shoelace formula gives the area from the corners alone.  Pick's theorem
+// then relates that area to the grid points the polygon touches:
+//
+//   area = interior + boundary/2 - 1
+//
+// so the count of grid points inside the polygon follows from the area and
+// the count of grid points along its edges.  When the grid points are the
+// centres of cells in a map, that's the number of cells enclosed by a loop
+// (interior) or dug out by tracing one (interior + boundary).
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Polygon {
+    vertices: Vec<(isize, isize)>,
+}
+
+impl Polygon {
+    // Corners in order around the outline.  The last connects back to the
+    // first, and points partway along an edge are allowed.
+    pub fn new(vertices: Vec<(isize, isize)>) -> Polygon {
+        Polygon { vertices }
+    }
+
+    // Outline traced from start by moving (delta, count) at a time, where
+    // delta is one step (e.g. (0, 1)) and count is the number of steps.
+    pub fn from_moves<I>(start: (isize, isize), moves: I) -> Polygon
+    where I: IntoIterator<Item = ((isize, isize), usize)> {
+        let mut vertices = vec![start];
+        let (mut x, mut y) = start;
+
+        for ((dx, dy), count) in moves {
+            x += dx * count as isize;
+            y += dy * count as isize;
+            vertices.push((x, y));
+        }
+
+        // A closed outline ends where it started.
+        if vertices.len() > 1 && vertices.last() == Some(&start) {
+            vertices.pop();
+        }
+
+        Polygon { vertices }
+    }
+
+    #[cfg(test)]
+    pub fn vertices(&self) -> &[(isize, isize)] {
+        &self.vertices
+    }
+
+    // Consecutive corner pairs, including the wrap around to the start.
+    fn edges(&self) -> impl Iterator<Item = ((isize, isize), (isize, isize))> + '_ {
+        let n = self.vertices.len();
+        (0..n).map(move |i| (self.vertices[i], self.vertices[(i + 1) % n]))
+    }
+
+    // Twice the signed area, which is always a whole number.  Positive when
+    // the outline runs counterclockwise with x to the right and y upward.
+    pub fn signed_area2(&self) -> isize {
+        self.edges()
+            .map(|((x0, y0), (x1, y1))| x0*y1 - y0*x1)
+            .sum()
+    }
+
+    #[cfg(test)]
+    pub fn signed_area(&self) -> f64 {
+        self.signed_area2() as f64 / 2.0
+    }
+
+    // Grid points on the outline
+    pub fn boundary(&self) -> usize {
+        self.edges()
+            .map(|((x0, y0), (x1, y1))| gcd(x1 - x0, y1 - y0) as usize)
+            .sum()
+    }
+
+    // Grid points strictly inside the outline (Pick's theorem)
+    pub fn interior(&self) -> usize {
+        if self.vertices.len() < 3 {
+            return 0;
+        }
+
+        // Outlines that double back on themselves have no inside.
+        (self.signed_area2().unsigned_abs() + 2).saturating_sub(self.boundary()) / 2
+    }
+
+    // Grid points inside or on the outline
+    pub fn total(&self) -> usize {
+        self.interior() + self.boundary()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Polygon;
+
+    #[test]
+    fn test_square() {
+        let square = Polygon::new(vec![(0, 0), (0, 2), (2, 2), (2, 0)]);
+
+        assert_eq!(square.signed_area2(), -8);
+        assert_eq!(square.signed_area(), -4.0);
+        assert_eq!(square.boundary(), 8);
+        assert_eq!(square.interior(), 1);
+        assert_eq!(square.total(), 9);
+
+        // Going the other way flips the sign only.
+        let reversed = Polygon::new(vec![(2, 0), (2, 2), (0, 2), (0, 0)]);
+        assert_eq!(reversed.signed_area2(), 8);
+        assert_eq!(reversed.total(), 9);
+    }
+
+    #[test]
+    fn test_triangle() {
+        // Diagonal edges pass through grid points every gcd(dx, dy) steps.
+        let triangle = Polygon::new(vec![(0, 0), (4, 0), (0, 6)]);
+
+        assert_eq!(triangle.signed_area2(), 24);
+        assert_eq!(triangle.boundary(), 4 + 2 + 6);
+        assert_eq!(triangle.interior(), 7);
+
+        let thin = Polygon::new(vec![(0, 0), (1, 0), (0, 1)]);
+        assert_eq!(thin.signed_area(), 0.5);
+        assert_eq!(thin.interior(), 0);
+        assert_eq!(thin.total(), 3);
+    }
+
+    #[test]
+    fn test_from_moves() {
+        // A 2x2 square with one unit corner cut out: a 3x3 block of points less one.
+        let shape = Polygon::from_moves((0, 0), vec![
+            ((0, 1), 1), ((1, 0), 1), ((0, 1), 1), ((1, 0), 1), ((0, -1), 2), ((-1, 0), 2),
+        ]);
+
+        assert_eq!(shape.vertices().len(), 6);
+        assert_eq!(shape.boundary(), 8);
+        assert_eq!(shape.interior(), 0);
+        assert_eq!(shape.total(), 8);
+
+        let big = Polygon::from_moves((5, -3), vec![
+            ((1, 0), 10), ((0, 1), 10), ((-1, 0), 10), ((0, -1), 10),
+        ]);
+        assert_eq!(big.vertices().len(), 4);
+        assert_eq!(big.boundary(), 40);
+        assert_eq!(big.interior(), 81);
+        assert_eq!(big.total(), 121);
+    }
+
+    #[test]
+    fn test_collinear_points() {
+        // Extra points along an edge don't change anything.
+        let plain = Polygon::new(vec![(0, 0), (0, 3), (3, 3), (3, 0)]);
+        let dotted = Polygon::new(vec![(0, 0), (0, 1), (0, 2), (0, 3), (3, 3), (3, 0)]);
+
+        assert_eq!(plain.signed_area2(), dotted.signed_area2());
+        assert_eq!(plain.boundary(), dotted.boundary());
+        assert_eq!(plain.interior(), dotted.interior());
+    }
+}